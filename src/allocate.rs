@@ -0,0 +1,610 @@
+//! Exact multi-region CP allocation.
+//!
+//! Every region's dominant chains from `all_lodging_storage.json` form one group of a
+//! multiple-choice knapsack: exactly one chain is chosen per region (the empty chain when the
+//! region isn't used). Regions may be given their own minimums and the choice is made either to
+//! reach global storage/lodging totals for the least CP or to get the most storage (or lodging)
+//! within a global CP budget.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Ok, Result};
+use comfy_table::{Attribute, Cell, Table};
+use indexmap::IndexMap;
+use regex::Regex;
+
+use crate::cli_args::{Cli, ContextType};
+use crate::houseinfo::*;
+use crate::list_buildings::Chain;
+use crate::name_resolution::resolve_region_name;
+
+pub(crate) type RegionChainsMap = IndexMap<usize, IndexMap<usize, Vec<Chain>>>;
+
+/// Storage and lodging counts given as `S20L4`, `S20` or `L4`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct UsageTarget {
+    pub storage: u16,
+    pub lodging: u16,
+}
+
+impl UsageTarget {
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        lazy_static! {
+            static ref TARGET: Regex =
+                Regex::new(r"^(?i)\s*(?:S\s*(\d+))?\s*(?:L\s*(\d+))?\s*$").unwrap();
+        }
+        let (storage, lodging) = match TARGET.captures(text) {
+            Some(caps) if caps.get(1).is_some() || caps.get(2).is_some() => {
+                (caps.get(1), caps.get(2))
+            }
+            _ => bail!(
+                "Unable to read '{}' as storage and lodging (e.g. S20L4).",
+                text
+            ),
+        };
+        Ok(Self {
+            storage: storage.map_or(Ok(0), |m| Ok(m.as_str().parse()?))?,
+            lodging: lodging.map_or(Ok(0), |m| Ok(m.as_str().parse()?))?,
+        })
+    }
+}
+
+pub(crate) fn all_from_lodging_storage_json() -> Result<RegionChainsMap> {
+    let filename = "all_lodging_storage.json";
+    let path = PathBuf::from("./data/housecraft").join(filename);
+    let file = File::open(&path).with_context(|| format!("Can't find {}", path.display()))?;
+    let reader = BufReader::new(file);
+    let chains = serde_json::from_reader(reader)?;
+    Ok(chains)
+}
+
+fn parse_region_minimums(
+    cli: &Cli,
+    regions_buildings: &RegionBuildingMap,
+) -> Result<BTreeMap<usize, UsageTarget>> {
+    let mut minimums = BTreeMap::new();
+    let Some(spec) = cli.region_min.as_ref() else {
+        return Ok(minimums);
+    };
+    for entry in spec.split(',').filter(|s| !s.trim().is_empty()) {
+        let Some((region, target)) = entry.rsplit_once(':') else {
+            bail!("Expected REGION:S<n>L<n> in '{}'.", entry);
+        };
+        let region_name = resolve_region_name(regions_buildings, region)?;
+        let region_key = regions_buildings[&region_name]
+            .values()
+            .next()
+            .unwrap()
+            .region_key;
+        minimums.insert(region_key, UsageTarget::parse(target)?);
+    }
+    Ok(minimums)
+}
+
+fn region_names(regions_buildings: &RegionBuildingMap) -> BTreeMap<usize, String> {
+    regions_buildings
+        .iter()
+        .filter_map(|(name, map)| map.values().next().map(|b| (b.region_key, name.clone())))
+        .collect()
+}
+
+/// Returns each region's chains meeting its minimum, sorted by cost.
+fn region_options<'a>(
+    region_chains: &'a RegionChainsMap,
+    minimums: &BTreeMap<usize, UsageTarget>,
+    region_names: &BTreeMap<usize, String>,
+) -> Result<Vec<(usize, Vec<&'a Chain>)>> {
+    let region_name = |region_key: &usize| {
+        region_names
+            .get(region_key)
+            .cloned()
+            .unwrap_or_else(|| region_key.to_string())
+    };
+    let mut options = vec![];
+    for (region_key, lodgings) in region_chains.iter() {
+        let minimum = minimums.get(region_key).copied().unwrap_or_default();
+        let mut chains: Vec<&Chain> = lodgings
+            .values()
+            .flatten()
+            .filter(|c| c.storage >= minimum.storage && c.lodging >= minimum.lodging)
+            .collect();
+        if chains.is_empty() {
+            bail!(
+                "No chain in {} provides {} storage and {} lodging.",
+                region_name(region_key),
+                minimum.storage,
+                minimum.lodging
+            );
+        }
+        chains.sort_by_key(|c| (c.cost, std::cmp::Reverse((c.storage, c.lodging))));
+        options.push((*region_key, chains));
+    }
+    for region_key in minimums.keys() {
+        if !region_chains.contains_key(region_key) {
+            bail!(
+                "{} has no chains in all_lodging_storage.json.",
+                region_name(region_key)
+            );
+        }
+    }
+    Ok(options)
+}
+
+/// Per region option choices and the state they were taken from, for backtracking.
+type Choices = Vec<Vec<(u32, u32)>>;
+
+const UNSET: i64 = i64::MIN;
+
+fn backtrack(choices: &Choices, mut state: usize) -> Vec<usize> {
+    let mut chosen = vec![0; choices.len()];
+    for (region, region_choices) in choices.iter().enumerate().rev() {
+        let (previous, option) = region_choices[state];
+        chosen[region] = option as usize;
+        state = previous as usize;
+    }
+    chosen
+}
+
+/// Chooses one chain per region reaching `target` storage and lodging for the least total CP.
+fn minimize_cost(options: &[(usize, Vec<&Chain>)], target: UsageTarget) -> Option<Vec<usize>> {
+    let width = target.lodging as usize + 1;
+    let num_states = (target.storage as usize + 1) * width;
+    let index = |s: usize, l: usize| s * width + l;
+
+    // Values are negated costs so that both modes maximize.
+    let mut best = vec![UNSET; num_states];
+    best[0] = 0;
+    let mut choices: Choices = vec![];
+
+    for (_, chains) in options.iter() {
+        // Capped at the target, cheaper chains make the rest of the region's chains redundant.
+        let mut capped: Vec<(usize, usize, i64, u32)> = vec![];
+        for (i, chain) in chains.iter().enumerate() {
+            let s = std::cmp::min(chain.storage, target.storage) as usize;
+            let l = std::cmp::min(chain.lodging, target.lodging) as usize;
+            if !capped.iter().any(|c| c.0 >= s && c.1 >= l) {
+                capped.push((s, l, chain.cost as i64, i as u32));
+            }
+        }
+
+        let mut next = vec![UNSET; num_states];
+        let mut region_choices = vec![(0, 0); num_states];
+        for s in 0..=target.storage as usize {
+            for l in 0..width {
+                let value = best[index(s, l)];
+                if value == UNSET {
+                    continue;
+                }
+                for (cs, cl, cost, option) in capped.iter() {
+                    let ns = std::cmp::min(s + cs, target.storage as usize);
+                    let nl = std::cmp::min(l + cl, target.lodging as usize);
+                    let candidate = value - cost;
+                    if candidate > next[index(ns, nl)] {
+                        next[index(ns, nl)] = candidate;
+                        region_choices[index(ns, nl)] = (index(s, l) as u32, *option);
+                    }
+                }
+            }
+        }
+        best = next;
+        choices.push(region_choices);
+    }
+
+    let goal = index(target.storage as usize, target.lodging as usize);
+    if best[goal] == UNSET {
+        return None;
+    }
+    Some(backtrack(&choices, goal))
+}
+
+/// Returns the chain's (context, other) usage counts.
+fn context_counts(context: &ContextType, chain: &Chain) -> (u16, u16) {
+    match context {
+        ContextType::S | ContextType::Storage => (chain.storage, chain.lodging),
+        ContextType::L | ContextType::Lodging => (chain.lodging, chain.storage),
+    }
+}
+
+/// Chooses one chain per region maximizing the context's usage count within `budget` CP while
+/// meeting `minimum` of the other usage.
+fn maximize_value(
+    options: &[(usize, Vec<&Chain>)],
+    budget: usize,
+    minimum: u16,
+    context: &ContextType,
+) -> Option<Vec<usize>> {
+    let width = minimum as usize + 1;
+    let num_states = (budget + 1) * width;
+    let index = |cost: usize, other: usize| cost * width + other;
+
+    let mut best = vec![UNSET; num_states];
+    best[0] = 0;
+    let mut choices: Choices = vec![];
+
+    for (_, chains) in options.iter() {
+        let mut next = vec![UNSET; num_states];
+        let mut region_choices = vec![(0, 0); num_states];
+        for cost in 0..=budget {
+            for other in 0..width {
+                let value = best[index(cost, other)];
+                if value == UNSET {
+                    continue;
+                }
+                for (option, chain) in chains.iter().enumerate() {
+                    let next_cost = cost + chain.cost as usize;
+                    if next_cost > budget {
+                        break;
+                    }
+                    let (chain_value, chain_other) = context_counts(context, chain);
+                    let next_other = std::cmp::min(other + chain_other as usize, minimum as usize);
+                    let candidate = value + chain_value as i64;
+                    if candidate > next[index(next_cost, next_other)] {
+                        next[index(next_cost, next_other)] = candidate;
+                        region_choices[index(next_cost, next_other)] =
+                            (index(cost, other) as u32, option as u32);
+                    }
+                }
+            }
+        }
+        best = next;
+        choices.push(region_choices);
+    }
+
+    // The best value at the least cost wins.
+    let goal = (0..=budget)
+        .map(|cost| index(cost, minimum as usize))
+        .filter(|i| best[*i] != UNSET)
+        .max_by_key(|i| (best[*i], std::cmp::Reverse(*i)))?;
+    Some(backtrack(&choices, goal))
+}
+
+fn print_allocation(
+    options: &[(usize, Vec<&Chain>)],
+    chosen: &[usize],
+    regions_buildings: &RegionBuildingMap,
+) {
+    let buildings: BuildingMap = regions_buildings
+        .values()
+        .flat_map(|map| map.iter().map(|(k, b)| (*k, b.clone())))
+        .collect();
+    let region_names = region_names(regions_buildings);
+
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Region").add_attribute(Attribute::Dim),
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut summary = Table::new();
+    summary.load_preset(HOUSECRAFT_TABLE_STYLE);
+    summary.set_header(vec![
+        Cell::new("Region").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut totals = UsageCounters::new();
+    for ((region_key, chains), option) in options.iter().zip(chosen.iter()) {
+        let chain = chains[*option];
+        if chain.indices.len() < 2 {
+            continue;
+        }
+        let region_name = region_names
+            .get(region_key)
+            .cloned()
+            .unwrap_or_else(|| region_key.to_string());
+
+        let mut region_name_value = region_name.clone();
+        for (index, state) in chain.indices.iter().zip(chain.states.iter()).skip(1) {
+            let building = buildings.get(index).unwrap();
+            let (warehouse_count, worker_count) = if *state == 1 {
+                (building.warehouse_count.to_string(), "".to_string())
+            } else {
+                ("".to_string(), building.worker_count.to_string())
+            };
+            table.add_row(vec![
+                &region_name_value,
+                &building.key.to_string(),
+                &building.building_name,
+                &building.cost.to_string(),
+                &warehouse_count,
+                &worker_count,
+            ]);
+            region_name_value = "".to_string();
+        }
+
+        summary.add_row(vec![
+            &region_name,
+            &chain.cost.to_string(),
+            &chain.storage.to_string(),
+            &chain.lodging.to_string(),
+        ]);
+        totals.cost += chain.cost as usize;
+        totals.warehouse_count += chain.storage as usize;
+        totals.worker_count += chain.lodging as usize;
+    }
+
+    summary.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new(totals.cost).add_attribute(Attribute::Bold),
+        Cell::new(totals.warehouse_count).add_attribute(Attribute::Bold),
+        Cell::new(totals.worker_count).add_attribute(Attribute::Bold),
+    ]);
+
+    println!("\n{table}");
+    println!("{summary}");
+}
+
+/// Allocates CP across regions by choosing exactly one dominant chain per region.
+pub(crate) fn allocate(cli: &Cli) -> Result<()> {
+    let regions_buildings = parse_houseinfo_data()?;
+    let region_chains = all_from_lodging_storage_json()?;
+    let minimums = parse_region_minimums(cli, &regions_buildings)?;
+    let options = region_options(&region_chains, &minimums, &region_names(&regions_buildings))?;
+
+    let target = UsageTarget {
+        storage: cli.storage.unwrap_or(0),
+        lodging: cli.lodging.unwrap_or(0),
+    };
+    let chosen = match cli.budget {
+        Some(budget) => {
+            let context = cli.context.clone().unwrap_or(ContextType::Storage);
+            let (minimum, primary_minimum) = match context {
+                ContextType::S | ContextType::Storage => (target.lodging, target.storage),
+                ContextType::L | ContextType::Lodging => (target.storage, target.lodging),
+            };
+            maximize_value(&options, budget, minimum, &context).filter(|chosen| {
+                let total: u16 = options
+                    .iter()
+                    .zip(chosen.iter())
+                    .map(|((_, chains), i)| context_counts(&context, chains[*i]).0)
+                    .sum();
+                total >= primary_minimum
+            })
+        }
+        None => minimize_cost(&options, target),
+    };
+
+    let Some(chosen) = chosen else {
+        bail!("No allocation satisfies the requested minimums.");
+    };
+    print_allocation(&options, &chosen, &regions_buildings);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(cost: u16, storage: u16, lodging: u16) -> Chain {
+        Chain {
+            lodging,
+            storage,
+            cost,
+            indices: vec![0],
+            states: vec![0],
+            alternatives: vec![],
+        }
+    }
+
+    /// Three regions' options sorted by cost, each starting with the unused (empty) chain.
+    fn fixture() -> Vec<Vec<Chain>> {
+        vec![
+            vec![
+                chain(0, 0, 0),
+                chain(1, 3, 0),
+                chain(2, 0, 2),
+                chain(4, 8, 1),
+            ],
+            vec![
+                chain(0, 0, 0),
+                chain(2, 5, 0),
+                chain(3, 2, 3),
+                chain(6, 12, 0),
+            ],
+            vec![chain(0, 0, 0), chain(1, 0, 1), chain(5, 9, 2)],
+        ]
+    }
+
+    fn options(regions: &[Vec<Chain>]) -> Vec<(usize, Vec<&Chain>)> {
+        regions
+            .iter()
+            .enumerate()
+            .map(|(key, chains)| (key, chains.iter().collect()))
+            .collect()
+    }
+
+    /// Every combination of one option per region as (cost, storage, lodging, choices).
+    fn combinations(options: &[(usize, Vec<&Chain>)]) -> Vec<(usize, usize, usize, Vec<usize>)> {
+        let mut combinations = vec![(0, 0, 0, vec![])];
+        for (_, chains) in options.iter() {
+            let mut next = vec![];
+            for (cost, storage, lodging, chosen) in combinations.iter() {
+                for (i, chain) in chains.iter().enumerate() {
+                    let mut chosen = chosen.clone();
+                    chosen.push(i);
+                    next.push((
+                        cost + chain.cost as usize,
+                        storage + chain.storage as usize,
+                        lodging + chain.lodging as usize,
+                        chosen,
+                    ));
+                }
+            }
+            combinations = next;
+        }
+        combinations
+    }
+
+    fn totals(options: &[(usize, Vec<&Chain>)], chosen: &[usize]) -> (usize, usize, usize) {
+        options
+            .iter()
+            .zip(chosen.iter())
+            .map(|((_, chains), i)| chains[*i])
+            .fold((0, 0, 0), |(c, s, l), chain| {
+                (
+                    c + chain.cost as usize,
+                    s + chain.storage as usize,
+                    l + chain.lodging as usize,
+                )
+            })
+    }
+
+    #[test]
+    fn parse_usage_targets() {
+        let target = |text| UsageTarget::parse(text).unwrap();
+        assert_eq!(
+            target("S20L4"),
+            UsageTarget {
+                storage: 20,
+                lodging: 4
+            }
+        );
+        assert_eq!(
+            target("s20 l4"),
+            UsageTarget {
+                storage: 20,
+                lodging: 4
+            }
+        );
+        assert_eq!(
+            target("S20"),
+            UsageTarget {
+                storage: 20,
+                lodging: 0
+            }
+        );
+        assert_eq!(
+            target(" L3 "),
+            UsageTarget {
+                storage: 0,
+                lodging: 3
+            }
+        );
+        assert_eq!(target("S0L0"), UsageTarget::default());
+        assert!(UsageTarget::parse("").is_err());
+        assert!(UsageTarget::parse("L4S20").is_err());
+        assert!(UsageTarget::parse("S").is_err());
+        assert!(UsageTarget::parse("S-1").is_err());
+        assert!(UsageTarget::parse("S70000").is_err());
+    }
+
+    #[test]
+    fn minimize_cost_matches_exhaustive_search() {
+        let regions = fixture();
+        let options = options(&regions);
+        let combinations = combinations(&options);
+        for storage in 0..=30 {
+            for lodging in 0..=7 {
+                let target = UsageTarget { storage, lodging };
+                let expected = combinations
+                    .iter()
+                    .filter(|(_, s, l, _)| *s >= storage as usize && *l >= lodging as usize)
+                    .map(|(c, _, _, _)| *c)
+                    .min();
+                let chosen = minimize_cost(&options, target);
+                match (expected, chosen) {
+                    (None, None) => {}
+                    (Some(cost), Some(chosen)) => {
+                        let (c, s, l) = totals(&options, &chosen);
+                        assert_eq!(c, cost, "cost for S{storage}L{lodging}");
+                        assert!(s >= storage as usize && l >= lodging as usize);
+                    }
+                    (expected, chosen) => {
+                        panic!("S{storage}L{lodging}: expected {expected:?}, got {chosen:?}")
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn minimize_cost_of_nothing_is_the_empty_allocation() {
+        let regions = fixture();
+        let options = options(&regions);
+        assert_eq!(
+            minimize_cost(&options, UsageTarget::default()),
+            Some(vec![0, 0, 0])
+        );
+        assert_eq!(minimize_cost(&[], UsageTarget::default()), Some(vec![]));
+        assert_eq!(
+            minimize_cost(
+                &[],
+                UsageTarget {
+                    storage: 1,
+                    lodging: 0
+                }
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn maximize_value_matches_exhaustive_search() {
+        let regions = fixture();
+        let options = options(&regions);
+        let combinations = combinations(&options);
+        for budget in 0..=16 {
+            for minimum in 0..=4 {
+                for context in [ContextType::Storage, ContextType::Lodging] {
+                    let value = |s: usize, l: usize| match context {
+                        ContextType::S | ContextType::Storage => (s, l),
+                        ContextType::L | ContextType::Lodging => (l, s),
+                    };
+                    // The most value, then the least cost reaching it.
+                    let expected = combinations
+                        .iter()
+                        .filter(|(c, s, l, _)| *c <= budget && value(*s, *l).1 >= minimum)
+                        .map(|(c, s, l, _)| (value(*s, *l).0, std::cmp::Reverse(*c)))
+                        .max();
+                    let chosen = maximize_value(&options, budget, minimum as u16, &context);
+                    match (expected, chosen) {
+                        (None, None) => {}
+                        (Some((best, std::cmp::Reverse(cost))), Some(chosen)) => {
+                            let (c, s, l) = totals(&options, &chosen);
+                            assert_eq!((value(s, l).0, c), (best, cost));
+                            assert!(value(s, l).1 >= minimum);
+                        }
+                        (expected, chosen) => {
+                            panic!("budget {budget} minimum {minimum}: expected {expected:?}, got {chosen:?}")
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn region_options_report_region_names() {
+        let mut region_chains = RegionChainsMap::new();
+        region_chains.insert(5, IndexMap::from([(0, vec![chain(0, 0, 0)])]));
+        let region_names = BTreeMap::from([(5, "Heidel".to_string()), (7, "Velia".to_string())]);
+
+        let minimums = BTreeMap::from([(
+            5,
+            UsageTarget {
+                storage: 1,
+                lodging: 0,
+            },
+        )]);
+        let error = region_options(&region_chains, &minimums, &region_names)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("No chain in Heidel"), "{error}");
+
+        let minimums = BTreeMap::from([(7, UsageTarget::default())]);
+        let error = region_options(&region_chains, &minimums, &region_names)
+            .unwrap_err()
+            .to_string();
+        assert!(error.starts_with("Velia has no chains"), "{error}");
+    }
+}
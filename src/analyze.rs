@@ -0,0 +1,255 @@
+//! Building utility and tree statistics of a region.
+//!
+//! Counts how often each building appears in the region's dominant chains and in which state,
+//! from what cost on every chain includes it and which buildings never appear at all, not even
+//! in an equal-cost alternative (so are free for workshops), followed by the shape of the
+//! region's building tree.
+
+use std::collections::HashMap;
+
+use anyhow::{Ok, Result};
+use comfy_table::{Attribute, Cell, Table};
+
+use crate::cli_args::Cli;
+use crate::houseinfo::*;
+use crate::list_buildings::Chain;
+use crate::node_manipulation::{count_subtrees, count_subtrees_at, group_indices_by_value};
+use crate::region_nodes::RegionNodes;
+use crate::selection::BuildingSelection;
+
+const LARGEST_SUBTREES: usize = 5;
+
+#[derive(Clone, Debug, Default)]
+struct BuildingUsage {
+    chains: usize,
+    storage: usize,
+    lodging: usize,
+    /// In chains only as a prerequisite, in a state giving no storage or lodging.
+    pass_through: usize,
+    /// Chains without the building that have an equal-cost alternative with it.
+    alternatives: usize,
+    /// Highest cost of a chain, or one of its alternatives, without the building.
+    max_cost_without: u16,
+}
+
+fn building_usages(region: &RegionNodes, chains: &[Chain]) -> HashMap<usize, BuildingUsage> {
+    let mut usages: HashMap<usize, BuildingUsage> = region
+        .children
+        .iter()
+        .skip(1)
+        .map(|key| (*key, BuildingUsage::default()))
+        .collect();
+    for chain in chains.iter() {
+        for (key, usage) in usages.iter_mut() {
+            let in_alternatives: Vec<bool> = chain
+                .alternatives
+                .iter()
+                .map(|alternative| alternative.indices.contains(key))
+                .collect();
+            match chain.indices.iter().position(|k| k == key) {
+                Some(i) => {
+                    let building = &region.buildings[key];
+                    usage.chains += 1;
+                    match chain.states[i] {
+                        1 if building.warehouse_count > 0 => usage.storage += 1,
+                        2 if building.worker_count > 0 => usage.lodging += 1,
+                        _ => usage.pass_through += 1,
+                    }
+                }
+                None if in_alternatives.contains(&true) => usage.alternatives += 1,
+                None => {}
+            }
+            // The building isn't needed at the chain's cost when any equal-cost set goes without.
+            if !chain.indices.contains(key) || in_alternatives.contains(&false) {
+                usage.max_cost_without = usage.max_cost_without.max(chain.cost);
+            }
+        }
+    }
+    usages
+}
+
+fn print_usages(region: &RegionNodes, chains: &[Chain]) {
+    let usages = building_usages(region, chains);
+    let max_cost = chains.iter().map(|chain| chain.cost).max().unwrap_or(0);
+
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+        Cell::new("Chains").add_attribute(Attribute::Dim),
+        Cell::new("As S").add_attribute(Attribute::Dim),
+        Cell::new("As L").add_attribute(Attribute::Dim),
+        Cell::new("Pass").add_attribute(Attribute::Dim),
+        Cell::new("Alt").add_attribute(Attribute::Dim),
+        Cell::new("Always from C").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut never_used = vec![];
+    for key in region.children.iter().skip(1) {
+        let building = &region.buildings[key];
+        let usage = &usages[key];
+        let is_used = usage.chains > 0 || usage.alternatives > 0;
+        if !is_used {
+            never_used.push(building);
+        }
+        let count = |n: usize| {
+            if n > 0 {
+                n.to_string()
+            } else {
+                "".to_string()
+            }
+        };
+        let always_from = if usage.chains > 0 && usage.max_cost_without < max_cost {
+            (usage.max_cost_without + 1).to_string()
+        } else {
+            "".to_string()
+        };
+        let mut row = vec![
+            Cell::new(building.key),
+            Cell::new(&building.building_name),
+            Cell::new(building.cost),
+            Cell::new(building.warehouse_count),
+            Cell::new(building.worker_count),
+            Cell::new(format!(
+                "{} ({:.0}%)",
+                usage.chains,
+                100.0 * usage.chains as f64 / chains.len() as f64
+            )),
+            Cell::new(count(usage.storage)),
+            Cell::new(count(usage.lodging)),
+            Cell::new(count(usage.pass_through)),
+            Cell::new(count(usage.alternatives)),
+            Cell::new(always_from),
+        ];
+        if !is_used {
+            row.iter_mut()
+                .for_each(|cell| *cell = cell.clone().add_attribute(Attribute::Dim));
+        }
+        table.add_row(row);
+    }
+
+    table.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new(format!("{} dominant chains", chains.len())).add_attribute(Attribute::Bold),
+        Cell::new(region.usage_counts.cost).add_attribute(Attribute::Bold),
+        Cell::new(region.max_warehouse_count).add_attribute(Attribute::Bold),
+        Cell::new(region.max_worker_count).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+    ]);
+    println!("\n{table}");
+
+    if never_used.is_empty() {
+        println!("\nEvery building is in at least one dominant chain or alternative.");
+    } else {
+        println!(
+            "\nNever in a dominant chain or alternative (free for workshops): {} building(s)",
+            never_used.len()
+        );
+        for building in never_used.iter() {
+            println!("  {} {}", building.key, building.building_name);
+        }
+    }
+}
+
+fn print_tree_statistics(region: &RegionNodes) {
+    let child_indices = group_indices_by_value(&region.parents);
+    let depths: Vec<usize> = region
+        .children
+        .iter()
+        .skip(1)
+        .map(|key| building_ancestors(*key, &region.buildings).len() + 1)
+        .collect();
+    let branching: Vec<usize> = region
+        .children
+        .iter()
+        .map(|key| child_indices.get(key).map_or(0, |c| c.len()))
+        .collect();
+    let leaves = branching.iter().skip(1).filter(|b| **b == 0).count();
+
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Tree").add_attribute(Attribute::Dim),
+        Cell::new("").add_attribute(Attribute::Dim),
+    ]);
+    table.add_row(vec![
+        "Buildings".to_string(),
+        (region.num_nodes - 1).to_string(),
+    ]);
+    table.add_row(vec!["Chain roots".to_string(), branching[0].to_string()]);
+    table.add_row(vec!["Leaves".to_string(), leaves.to_string()]);
+    table.add_row(vec![
+        "Depth".to_string(),
+        depths.iter().max().unwrap_or(&0).to_string(),
+    ]);
+    table.add_row(vec![
+        "Mean depth".to_string(),
+        format!(
+            "{:.2}",
+            depths.iter().sum::<usize>() as f64 / depths.len().max(1) as f64
+        ),
+    ]);
+    table.add_row(vec![
+        "Max branching".to_string(),
+        branching.iter().skip(1).max().unwrap_or(&0).to_string(),
+    ]);
+    table.add_row(vec![
+        "Subtrees".to_string(),
+        count_subtrees(region.root, &region.parents, &region.children).to_string(),
+    ]);
+    println!("\n{table}");
+
+    // Subtree counts of every non-root building, the rentable sets rooted there.
+    let mut subtrees: Vec<(usize, f64)> = region
+        .children
+        .iter()
+        .skip(1)
+        .map(|key| {
+            let count = count_subtrees_at(*key, &child_indices, &region.children) - 1.0;
+            (*key, count)
+        })
+        .collect();
+    subtrees.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Largest subtrees").add_attribute(Attribute::Dim),
+        Cell::new("Buildings").add_attribute(Attribute::Dim),
+        Cell::new("Subtrees").add_attribute(Attribute::Dim),
+    ]);
+    for (key, count) in subtrees.iter().take(LARGEST_SUBTREES) {
+        let size = building_dependents(*key, &region.buildings).len() + 1;
+        table.add_row(vec![
+            key.to_string(),
+            region.buildings[key].building_name.clone(),
+            size.to_string(),
+            count.to_string(),
+        ]);
+    }
+    println!("\n{table}");
+}
+
+/// Prints how each building of the region is used by its dominant chains along with the
+/// region's tree statistics.
+pub(crate) fn analyze(cli: &Cli) -> Result<()> {
+    let region_buildings = get_region_buildings(cli.region.clone())?;
+    let (region_name, buildings) = region_buildings.first_key_value().unwrap();
+    let region = RegionNodes::new(buildings)?;
+    let selection = BuildingSelection::from_cli(cli)?.for_region(buildings)?;
+    let chains = Chain::many_from_selection_json(region_name, &selection)?;
+
+    print_usages(&region, &chains);
+    print_tree_statistics(&region);
+    Ok(())
+}
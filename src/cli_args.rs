@@ -1,5 +1,7 @@
 use clap::{ArgGroup, Parser};
 
+use crate::estimate::parse_duration;
+
 #[derive(clap::ValueEnum, Clone, Debug)]
 pub(crate) enum ContextType {
     S,
@@ -8,17 +10,33 @@ pub(crate) enum ContextType {
     Lodging,
 }
 
+#[derive(clap::ValueEnum, Clone, Debug, Default)]
+pub(crate) enum TreeFormat {
+    #[default]
+    Ascii,
+    Dot,
+}
+
+/// Secondary objective among chains of equal cost.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum TieBreak {
+    /// fewest buildings rented
+    Buildings,
+    /// fewest buildings rented only as a prerequisite (no storage or lodging from them)
+    PassThrough,
+    /// most storage
+    Storage,
+}
+
 /// A BDO buildings chain tool.
-/// (Calpheon City, Valencia City and Heidel are not available for exact score listings.)
 #[derive(Clone, Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 #[clap(group(
     ArgGroup::new("listing")
         .required(false)
         .multiple(true)
-        .args(&["storage", "lodging"]),
+        .args(&["storage", "lodging", "max_cost", "max_storage", "max_lodging", "all_dominant"]),
 ))]
-
 pub(crate) struct Cli {
     /// list warehouse regions
     #[arg(short = 'l', long)]
@@ -32,14 +50,73 @@ pub(crate) struct Cli {
     #[arg(short = 's', long)]
     pub(crate) list_storage: bool,
 
-    /// find buildings for craft
+    /// list all regions lodging sorted by global efficiency (same as --list-storage --context lodging)
+    #[arg(long)]
+    pub(crate) list_lodging: bool,
+
+    /// find buildings for craft ranked by total CP (e.g. "Refinery 2" or "Refinery>=2")
     #[arg(short = 'f', long)]
     pub(crate) find_craft: Option<String>,
 
+    /// find the cheapest building set for a list of usages (e.g. "Refinery 2, Wood Workshop 2")
+    #[arg(short = 'p', long)]
+    pub(crate) plan_crafts: Option<String>,
+
+    /// choose one chain per region for the least CP reaching -S/-L totals (or the most
+    /// storage/lodging within --budget)
+    #[arg(long, help_heading = Some("Planning"))]
+    pub(crate) allocate: bool,
+
+    /// global CP budget for --allocate
+    #[arg(long, requires = "allocate", help_heading = Some("Planning"))]
+    pub(crate) budget: Option<usize>,
+
+    /// per region minimums for --allocate (e.g. "Velia:S10L2,Heidel:L3")
+    #[arg(long, requires = "allocate", help_heading = Some("Planning"))]
+    pub(crate) region_min: Option<String>,
+
+    /// owned buildings as "key:state,..." or a file of "key:state" lines (state is storage,
+    /// lodging or other); plans the cheapest upgrade reaching -S/-L in the region
+    #[arg(long, requires = "region", conflicts_with = "generation", help_heading = Some("Planning"))]
+    pub(crate) owned: Option<String>,
+
+    /// keep the usage state of --owned buildings
+    #[arg(long, requires = "owned", help_heading = Some("Planning"))]
+    pub(crate) lock_owned: bool,
+
+    /// validate a housing profile file and show its per-region and total usage
+    #[arg(long, conflicts_with = "generation", help_heading = Some("Planning"))]
+    pub(crate) profile: Option<String>,
+
+    /// plan the release, switch and rent steps between two chains of a region
+    #[arg(long, requires_all = ["region", "from", "to"], help_heading = Some("Planning"))]
+    pub(crate) transition: bool,
+
+    /// starting storage and lodging (e.g. S20L4) or a housing profile file for --transition
+    #[arg(long, requires = "transition", help_heading = Some("Planning"))]
+    pub(crate) from: Option<String>,
+
+    /// storage and lodging to reach (e.g. S30L6) for --transition
+    #[arg(long, requires = "transition", help_heading = Some("Planning"))]
+    pub(crate) to: Option<String>,
+
+    /// plan growing chains of a region that never return a building, from the -S/-L minimums
+    /// up to the maximums
+    #[arg(long, requires = "region", conflicts_with = "generation", help_heading = Some("Planning"))]
+    pub(crate) upgrade_path: bool,
+
+    /// usage to rank or maximize (storage or lodging)
+    #[arg(long, value_enum)]
+    pub(crate) context: Option<ContextType>,
+
+    /// show a building's usages, prerequisite chain and dependents (key or name)
+    #[arg(short = 'b', long)]
+    pub(crate) show_building: Option<String>,
+
     #[clap(flatten)]
     pub(crate) verbose: clap_verbosity_flag::Verbosity,
 
-    /// specific region or ALL
+    /// specific region (key, name or unique name prefix) or ALL
     #[arg(short = 'R', long, help_heading = Some("Listing"))]
     pub(crate) region: Option<String>,
 
@@ -51,6 +128,64 @@ pub(crate) struct Cli {
     #[arg(short = 'L', long, group = "listing", help_heading = Some("Listing"))]
     pub(crate) lodging: Option<u16>,
 
+    /// cost maximum
+    #[arg(long, help_heading = Some("Listing"))]
+    pub(crate) max_cost: Option<u16>,
+
+    /// warehouse storage slots count maximum
+    #[arg(long, help_heading = Some("Listing"))]
+    pub(crate) max_storage: Option<u16>,
+
+    /// worker lodging slots count maximum
+    #[arg(long, help_heading = Some("Listing"))]
+    pub(crate) max_lodging: Option<u16>,
+
+    /// list every dominant chain within the -S/-L minimums and maximums sorted by cost
+    /// instead of only the cheapest
+    #[arg(long, help_heading = Some("Listing"))]
+    pub(crate) all_dominant: bool,
+
+    /// also list chains with other buildings costing at most N CP more than the cheapest
+    #[arg(long, value_name = "N", requires_all = ["region", "listing"], conflicts_with_all = ["max_cost", "max_storage", "max_lodging", "all_dominant"], help_heading = Some("Listing"))]
+    pub(crate) within: Option<u16>,
+
+    /// maximum number of chains listed with --within
+    #[arg(long, value_name = "K", default_value_t = 10, requires = "within", help_heading = Some("Listing"))]
+    pub(crate) limit: usize,
+
+    /// show each listed chain as a rental order with cumulative CP, storage and lodging
+    #[arg(long, requires = "listing", help_heading = Some("Listing"))]
+    pub(crate) schedule: bool,
+
+    /// show the cost of one storage or lodging slot more or less and the minimums each building
+    /// of the cheapest chain is needed for
+    #[arg(long, requires = "listing", conflicts_with_all = ["within", "all_dominant", "max_cost", "max_storage", "max_lodging"], help_heading = Some("Listing"))]
+    pub(crate) explain: bool,
+
+    /// keep chains solved live (for regions without current chains) in data/housecraft/cache
+    #[arg(long, requires = "listing", help_heading = Some("Listing"))]
+    pub(crate) cache: bool,
+
+    /// render the region's building tree (highlighting the -S/-L chain when given)
+    #[arg(long, requires = "region", help_heading = Some("Listing"))]
+    pub(crate) tree: bool,
+
+    /// show how often each building is in the region's dominant chains and the tree's shape
+    #[arg(long, requires = "region", conflicts_with = "generation", help_heading = Some("Listing"))]
+    pub(crate) analyze: bool,
+
+    /// chart the region's CP by storage for each lodging level
+    #[arg(long, requires = "region", conflicts_with = "generation", help_heading = Some("Listing"))]
+    pub(crate) plot: bool,
+
+    /// also write the --plot chart as an SVG file
+    #[arg(long, value_name = "FILE", requires = "plot", help_heading = Some("Listing"))]
+    pub(crate) svg: Option<String>,
+
+    /// building tree output format
+    #[arg(long, value_enum, default_value_t, requires = "tree", help_heading = Some("Listing"))]
+    pub(crate) format: TreeFormat,
+
     /// generate exact scored houseinfo building chains
     #[arg(long, group = "generation", requires = "region", conflicts_with = "listing", help_heading = Some("Generation"))]
     pub(crate) generate: bool,
@@ -64,11 +199,56 @@ pub(crate) struct Cli {
     #[arg(long, requires = "generation", conflicts_with = "generate", help_heading = Some("Generation"))]
     pub(crate) limit_warehouse: Option<Option<usize>>,
 
+    /// buildings (keys or names, comma separated) never used for storage/lodging along with
+    /// their dependents; chains are written to and listed from data/housecraft/custom
+    #[arg(long, requires = "region", help_heading = Some("Generation"))]
+    pub(crate) exclude: Option<String>,
+
+    /// buildings (keys or names, comma separated) always used for storage/lodging along with
+    /// their prerequisites; chains are written to and listed from data/housecraft/custom
+    #[arg(long, requires = "region", help_heading = Some("Generation"))]
+    pub(crate) include: Option<String>,
+
+    /// record up to CAP (default 32) equal-cost alternative building sets per chain
+    #[arg(long, value_name = "CAP", requires = "generation", help_heading = Some("Generation"))]
+    pub(crate) ties: Option<Option<usize>>,
+
+    /// prefer among equal-cost chains the one with the fewest buildings, fewest pass-through
+    /// buildings or most storage (--optimize only; generated chains already have the most)
+    #[arg(long, value_enum, requires = "generation", help_heading = Some("Generation"))]
+    pub(crate) tiebreak: Option<TieBreak>,
+
+    /// skip (with -R ALL) or refuse regions estimated to take longer than DURATION to generate
+    /// (e.g. 90s, 30m, 12h or 2d)
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, default_value = "1d", requires = "generate", help_heading = Some("Generation"))]
+    pub(crate) max_estimated_time: f64,
+
+    /// generate regions over --max-estimated-time anyway
+    #[arg(long, requires = "generate", help_heading = Some("Generation"))]
+    pub(crate) force: bool,
+
     /// output only the lodging, storage and cost to /data/housecraft/validation
     #[arg(long, requires = "generation", help_heading = Some("Generation"))]
     pub(crate) for_validation: bool,
 
-    /// use NUM parallel jobs
-    #[arg(short, long, requires = "generation", conflicts_with = "optimize", help_heading = Some("Generation"))]
+    /// rank all regions' storage and lodging chains into all_regions_storage.json and
+    /// all_regions_lodging.json
+    /// (run after '--optimize -R ALL', which also does this)
+    #[arg(long, help_heading = Some("Generation"))]
+    pub(crate) build_storage_index: bool,
+
+    /// use NUM threads (default all cores) shared by the regions run concurrently with -R ALL
+    #[arg(short, long, requires = "generation", help_heading = Some("Generation"))]
     pub(crate) jobs: Option<u8>,
 }
+
+impl Cli {
+    /// Returns the number of equal-cost alternatives to record per chain (0 when not recording).
+    pub(crate) fn tie_cap(&self) -> usize {
+        match self.ties {
+            Some(Some(cap)) => cap,
+            Some(None) => 32,
+            None => 0,
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! Estimated `--generate` run times.
+//!
+//! The generator visits every storage/lodging combination of a region's building tree, so its
+//! run time is the region's multistate subtree count over the combinations visited per second.
+//! The rate is measured on the current machine by running the region's own generator for a
+//! short time; regions finishing within that time are timed exactly. With `-R ALL` the regions
+//! share the thread pool, so each region's estimate assumes it has the pool to itself.
+//!
+//! Benchmarking runs before any region is generated, one region at a time, so regions whose
+//! run time at `MIN_RATE` is already within the time limit are only bounded, not measured.
+
+use std::fmt;
+
+/// How long the generator is run to measure a region's throughput.
+pub(crate) const BENCHMARK_MILLIS: u64 = 200;
+
+/// Combinations per second per thread that the generator outpaces on any machine, even in a
+/// debug build.
+pub(crate) const MIN_RATE: f64 = 1e6;
+
+#[derive(Clone, Debug)]
+pub(crate) struct RuntimeEstimate {
+    /// Combinations visited per second on a single thread.
+    pub(crate) rate: f64,
+    pub(crate) threads: usize,
+    /// Whether other regions run on the same threads, making `seconds` a lower bound.
+    pub(crate) shared: bool,
+    pub(crate) seconds: f64,
+    /// Whether the benchmark visited every combination, making `seconds` a measurement.
+    pub(crate) complete: bool,
+    /// Whether the rate was measured rather than assumed to be `MIN_RATE`.
+    pub(crate) measured: bool,
+}
+
+impl RuntimeEstimate {
+    pub(crate) fn new(
+        combinations: f64,
+        visited: usize,
+        elapsed: f64,
+        threads: usize,
+        shared: bool,
+    ) -> Self {
+        let complete = visited as f64 >= combinations;
+        let rate = visited as f64 / elapsed.max(f64::EPSILON);
+        let seconds = if complete {
+            elapsed
+        } else {
+            combinations / rate / threads.max(1) as f64
+        };
+        Self {
+            rate,
+            threads,
+            shared,
+            seconds,
+            complete,
+            measured: true,
+        }
+    }
+
+    /// Returns an upper bound on the run time at `MIN_RATE` without running the generator.
+    pub(crate) fn unmeasured(combinations: f64, threads: usize, shared: bool) -> Self {
+        Self {
+            rate: MIN_RATE,
+            threads,
+            shared,
+            seconds: combinations / MIN_RATE / threads.max(1) as f64,
+            complete: false,
+            measured: false,
+        }
+    }
+}
+
+impl fmt::Display for RuntimeEstimate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.complete {
+            return write!(f, "{} (measured)", format_duration(self.seconds));
+        }
+        if !self.measured {
+            return write!(
+                f,
+                "at most {} (not measured)",
+                format_duration(self.seconds)
+            );
+        }
+        write!(
+            f,
+            "about {} on {} thread(s) at {} combinations/s per thread{}",
+            format_duration(self.seconds),
+            self.threads,
+            format_count(self.rate),
+            if self.shared {
+                " with no other region running"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+fn format_count(count: f64) -> String {
+    match count {
+        c if c >= 1e9 => format!("{:.2}B", c / 1e9),
+        c if c >= 1e6 => format!("{:.1}M", c / 1e6),
+        c if c >= 1e3 => format!("{:.1}K", c / 1e3),
+        c => format!("{c:.0}"),
+    }
+}
+
+/// Formats `seconds` in the largest unit that keeps it at or above one.
+pub(crate) fn format_duration(seconds: f64) -> String {
+    const UNITS: [(f64, &str); 5] = [
+        (365.0 * 86400.0, "years"),
+        (86400.0, "days"),
+        (3600.0, "hours"),
+        (60.0, "minutes"),
+        (1.0, "seconds"),
+    ];
+    if seconds < 1.0 {
+        return format!("{:.0}ms", seconds * 1000.0);
+    }
+    let (size, unit) = UNITS
+        .iter()
+        .find(|(size, _)| seconds >= *size)
+        .unwrap_or(&UNITS[4]);
+    match seconds / size {
+        value if value >= 1e4 => format!("{value:.1e} {unit}"),
+        value => format!("{value:.1} {unit}"),
+    }
+}
+
+/// Parses a duration such as `90`, `90s`, `30m`, `12h` or `2d` into seconds.
+pub(crate) fn parse_duration(text: &str) -> Result<f64, String> {
+    let text = text.trim();
+    let (number, scale) = match text.char_indices().last() {
+        Some((i, 's')) => (&text[..i], 1.0),
+        Some((i, 'm')) => (&text[..i], 60.0),
+        Some((i, 'h')) => (&text[..i], 3600.0),
+        Some((i, 'd')) => (&text[..i], 86400.0),
+        _ => (text, 1.0),
+    };
+    match number.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() && value >= 0.0 => Ok(value * scale),
+        _ => Err(format!(
+            "'{text}' is not a duration (e.g. 90, 90s, 30m, 12h or 2d)"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_reads_units_and_bare_seconds() {
+        assert_eq!(parse_duration("90"), Ok(90.0));
+        assert_eq!(parse_duration("90s"), Ok(90.0));
+        assert_eq!(parse_duration("30m"), Ok(1800.0));
+        assert_eq!(parse_duration("12h"), Ok(43200.0));
+        assert_eq!(parse_duration("2d"), Ok(172800.0));
+        assert_eq!(parse_duration("1.5h"), Ok(5400.0));
+        assert_eq!(parse_duration(" 0 "), Ok(0.0));
+        assert_eq!(parse_duration("2 d"), Ok(172800.0));
+    }
+
+    #[test]
+    fn parse_duration_rejects_other_text() {
+        for text in ["", "h", "-1h", "1w", "1ms", "inf", "NaN", "twelve hours"] {
+            let error = parse_duration(text).unwrap_err();
+            assert!(error.contains("is not a duration"), "{text}: {error}");
+        }
+    }
+
+    #[test]
+    fn format_duration_picks_the_largest_whole_unit() {
+        assert_eq!(format_duration(0.0412), "41ms");
+        assert_eq!(format_duration(1.0), "1.0 seconds");
+        assert_eq!(format_duration(90.0), "1.5 minutes");
+        assert_eq!(format_duration(43200.0), "12.0 hours");
+        assert_eq!(format_duration(86400.0 * 3.0), "3.0 days");
+        assert_eq!(format_duration(365.0 * 86400.0 * 2.0), "2.0 years");
+        assert_eq!(format_duration(365.0 * 86400.0 * 1.5e42), "1.5e42 years");
+    }
+
+    #[test]
+    fn runtime_estimate_scales_the_measured_rate_by_threads() {
+        let estimate = RuntimeEstimate::new(8000.0, 1000, 0.5, 4, false);
+        assert!(!estimate.complete);
+        assert_eq!(estimate.rate, 2000.0);
+        assert_eq!(estimate.seconds, 1.0);
+        assert_eq!(
+            estimate.to_string(),
+            "about 1.0 seconds on 4 thread(s) at 2.0K combinations/s per thread"
+        );
+        let estimate = RuntimeEstimate::new(8000.0, 1000, 0.5, 4, true);
+        assert!(estimate
+            .to_string()
+            .ends_with(" with no other region running"));
+    }
+
+    #[test]
+    fn runtime_estimate_is_measured_when_every_combination_was_visited() {
+        let estimate = RuntimeEstimate::new(1000.0, 1000, 0.25, 8, true);
+        assert!(estimate.complete);
+        assert_eq!(estimate.seconds, 0.25);
+        assert_eq!(estimate.to_string(), "250ms (measured)");
+    }
+
+    #[test]
+    fn unmeasured_estimates_assume_the_minimum_rate() {
+        let estimate = RuntimeEstimate::unmeasured(4.0 * MIN_RATE, 2, false);
+        assert!(!estimate.measured && !estimate.complete);
+        assert_eq!(estimate.seconds, 2.0);
+        assert_eq!(estimate.to_string(), "at most 2.0 seconds (not measured)");
+    }
+}
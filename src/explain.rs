@@ -0,0 +1,191 @@
+//! Marginal costs and binding buildings of a -S/-L query.
+//!
+//! The cheapest chains one storage or lodging slot either side of the query are read off the
+//! region's chains (or solved live when they aren't current) and each building of the chosen
+//! chain is checked for which minimum it, with everything requiring it, holds up.
+
+use comfy_table::{Attribute, Cell, Table};
+
+use crate::houseinfo::*;
+use crate::list_buildings::Chain;
+use crate::optimize::optimize_point;
+use crate::region_nodes::RegionNodes;
+use crate::selection::BuildingSelection;
+
+fn cheapest_chain(
+    region: &RegionNodes,
+    selection: &BuildingSelection,
+    front: Option<&[Chain]>,
+    storage: u16,
+    lodging: u16,
+) -> Option<Chain> {
+    match front {
+        Some(chains) => chains
+            .iter()
+            .filter(|chain| chain.storage >= storage && chain.lodging >= lodging)
+            .min_by_key(|chain| chain.cost)
+            .cloned(),
+        None => optimize_point(region, storage as usize, lodging as usize, selection),
+    }
+}
+
+fn print_marginal_costs(
+    region: &RegionNodes,
+    selection: &BuildingSelection,
+    front: Option<&[Chain]>,
+    chain: &Chain,
+    storage: u16,
+    lodging: u16,
+) {
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Query").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("Δ C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+    ]);
+    table.add_row(vec![
+        Cell::new(format!("-S {storage} -L {lodging}")).add_attribute(Attribute::Bold),
+        Cell::new(chain.cost).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new(chain.storage).add_attribute(Attribute::Bold),
+        Cell::new(chain.lodging).add_attribute(Attribute::Bold),
+    ]);
+
+    let mut neighbours = vec![(storage + 1, lodging), (storage, lodging + 1)];
+    if storage > 0 {
+        neighbours.push((storage - 1, lodging));
+    }
+    if lodging > 0 {
+        neighbours.push((storage, lodging - 1));
+    }
+    for (s, l) in neighbours {
+        let query = format!("-S {s} -L {l}");
+        match cheapest_chain(region, selection, front, s, l) {
+            Some(neighbour) => {
+                let delta = neighbour.cost as isize - chain.cost as isize;
+                table.add_row(vec![
+                    query,
+                    neighbour.cost.to_string(),
+                    format!("{delta:+}"),
+                    neighbour.storage.to_string(),
+                    neighbour.lodging.to_string(),
+                ]);
+            }
+            None => {
+                table.add_row(vec![query, "".to_string(), "infeasible".to_string()]);
+            }
+        }
+    }
+    println!("{table}");
+}
+
+fn print_binding_buildings(region: &RegionNodes, chain: &Chain, storage: u16, lodging: u16) {
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+        Cell::new("Dependents").add_attribute(Attribute::Dim),
+        Cell::new("Binds").add_attribute(Attribute::Dim),
+    ]);
+
+    let slots = |key: &usize, state: &usize| {
+        let building = &region.buildings[key];
+        if *state == 1 {
+            (building.warehouse_count, 0)
+        } else {
+            (0, building.worker_count)
+        }
+    };
+    let chain_buildings: Vec<(usize, usize)> = chain
+        .indices
+        .iter()
+        .copied()
+        .zip(chain.states.iter().copied())
+        .skip(1)
+        .collect();
+    for (key, state) in chain_buildings.iter() {
+        let building = &region.buildings[key];
+        // Dropping a building drops everything in the chain that requires it.
+        let dependents: Vec<&(usize, usize)> = building_dependents(*key, &region.buildings)
+            .iter()
+            .filter_map(|(_, b)| chain_buildings.iter().find(|(k, _)| *k == b.key))
+            .collect();
+        let (mut lost_storage, mut lost_lodging) = slots(key, state);
+        for (k, s) in dependents.iter() {
+            let (s, l) = slots(k, s);
+            lost_storage += s;
+            lost_lodging += l;
+        }
+        let binds_storage = (chain.storage as usize) < storage as usize + lost_storage;
+        let binds_lodging = (chain.lodging as usize) < lodging as usize + lost_lodging;
+        let binds = match (binds_storage, binds_lodging) {
+            (true, true) => "storage, lodging",
+            (true, false) => "storage",
+            (false, true) => "lodging",
+            (false, false) => "",
+        };
+        let (warehouse_count, worker_count) = slots(key, state);
+        table.add_row(vec![
+            building.key.to_string(),
+            building.building_name.clone(),
+            building.cost.to_string(),
+            if *state == 1 {
+                warehouse_count.to_string()
+            } else {
+                "".to_string()
+            },
+            if *state == 1 {
+                "".to_string()
+            } else {
+                worker_count.to_string()
+            },
+            if dependents.is_empty() {
+                "".to_string()
+            } else {
+                dependents.len().to_string()
+            },
+            binds.to_string(),
+        ]);
+    }
+
+    table.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new(format!(
+            "surplus S +{} L +{}",
+            chain.storage - storage,
+            chain.lodging - lodging
+        ))
+        .add_attribute(Attribute::Bold),
+        Cell::new(chain.cost).add_attribute(Attribute::Bold),
+        Cell::new(chain.storage).add_attribute(Attribute::Bold),
+        Cell::new(chain.lodging).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+    ]);
+    println!("{table}");
+}
+
+/// Prints what one storage or lodging slot more or less than the query costs and which
+/// minimums each building of `chain` (with its dependents in the chain) is needed for.
+///
+/// `front` is the region's chains when current; otherwise the neighbouring queries are solved
+/// within the region `selection`.
+pub(crate) fn print_explanation(
+    region: &RegionNodes,
+    selection: &BuildingSelection,
+    front: Option<&[Chain]>,
+    chain: &Chain,
+    storage: u16,
+    lodging: u16,
+) {
+    print_marginal_costs(region, selection, front, chain, storage, lodging);
+    println!();
+    print_binding_buildings(region, chain, storage, lodging);
+}
@@ -1,120 +1,236 @@
 use super::houseinfo::*;
-use anyhow::{Ok, Result};
+use anyhow::{bail, Ok, Result};
 use comfy_table::{Attribute, Cell, Row, Table};
-use console::style;
-use std::collections::BTreeMap;
+use regex::Regex;
 
-type RegionCraftMap = BTreeMap<String, CraftBuildingMap>;
+use crate::name_resolution::normalize_name;
 
-lazy_static! {
-    static ref CRAFT_USAGE: IndexedStringMap =
-        read_csv_data("HouseInfoReceipe.csv").expect("Error reading HouseInfoReceipe.csv");
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum LevelBound {
+    Any,
+    Exactly(u32),
+    AtLeast(u32),
+    AtMost(u32),
 }
 
-fn print_listing(crafts_summary: RegionCraftMap) -> Result<()> {
-    let header_row = Row::from(vec![
-        Cell::new("Crafting Usage").add_attribute(Attribute::Dim),
-        Cell::new("Key").add_attribute(Attribute::Dim),
-        Cell::new("Building").add_attribute(Attribute::Dim),
-        Cell::new("Cost").add_attribute(Attribute::Dim),
-    ]);
-
-    for (region, usages) in crafts_summary.iter() {
-        let mut table = Table::new();
-        table.load_preset(HOUSECRAFT_TABLE_STYLE);
-        table.set_header(header_row.clone());
-
-        for (usage, buildings) in usages.iter() {
-            for building in buildings.1.iter() {
-                table.add_row(vec![
-                    usage,
-                    &building.key.to_string(),
-                    &building.building_name.to_string(),
-                    &building.cost.to_string(),
-                ]);
-            }
+impl LevelBound {
+    fn contains(&self, level: u32) -> bool {
+        match *self {
+            LevelBound::Any => true,
+            LevelBound::Exactly(n) => level == n,
+            LevelBound::AtLeast(n) => level >= n,
+            LevelBound::AtMost(n) => level <= n,
         }
-        println!("\n{}", style(region).bold());
-        println!("{table}");
     }
+}
 
-    Ok(())
+/// A crafting usage query such as `Refinery`, `Refinery 2` or `Refinery>=2`.
+#[derive(Clone, Debug)]
+pub(crate) struct CraftQuery {
+    pub text: String,
+    pub usage: String,
+    pub level: LevelBound,
 }
 
-fn insert_craft_building(
-    region_crafts: &mut CraftBuildingMap,
-    craft: &CraftList,
-    building: &Building,
-) {
-    if let Some(usage) = CRAFT_USAGE.get(&craft.item_craft_index) {
-        let craft_name = format!("{} {}", usage, craft.house_level);
-        if let Some(craft) = region_crafts.get_mut(&craft_name) {
-            craft.0 += 1;
-            craft.1.push(building.clone())
+impl CraftQuery {
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        lazy_static! {
+            static ref RANGE: Regex = Regex::new(r"^(.*?)\s*(>=|<=|=|>|<)\s*(\d+)$").unwrap();
+            static ref LEVEL: Regex = Regex::new(r"^(.*?)\s+(\d+)$").unwrap();
+        }
+        let trimmed = text.trim();
+        let (usage, level) = if let Some(caps) = RANGE.captures(trimmed) {
+            let Some(n) = caps[3].parse::<u32>().ok() else {
+                bail!("The crafting level in '{}' is too large.", text);
+            };
+            let level = match &caps[2] {
+                ">=" => LevelBound::AtLeast(n),
+                ">" => match n.checked_add(1) {
+                    Some(n) => LevelBound::AtLeast(n),
+                    None => bail!("No crafting level satisfies '{}'.", text),
+                },
+                "<=" => LevelBound::AtMost(n),
+                "<" if n > 0 => LevelBound::AtMost(n - 1),
+                "=" => LevelBound::Exactly(n),
+                _ => bail!("No crafting level satisfies '{}'.", text),
+            };
+            (caps[1].to_string(), level)
+        } else if let Some(caps) = LEVEL.captures(trimmed) {
+            let Some(n) = caps[2].parse::<u32>().ok() else {
+                bail!("The crafting level in '{}' is too large.", text);
+            };
+            (caps[1].to_string(), LevelBound::Exactly(n))
         } else {
-            region_crafts.insert(craft_name, (1, vec![building.clone()]));
+            (trimmed.to_string(), LevelBound::Any)
+        };
+        if usage.trim().is_empty() {
+            bail!("Missing crafting usage in '{}'.", text);
         }
+        Ok(Self {
+            text: trimmed.to_string(),
+            usage: normalize_name(&usage),
+            level,
+        })
     }
-}
 
-fn summarize_craft_buildings(regions_buildings: RegionBuildingMap) -> Result<RegionCraftMap> {
-    let mut crafts_summary = RegionCraftMap::new();
-    for (region, buildings) in regions_buildings.iter() {
-        let mut region_crafts = CraftBuildingMap::new();
-        for (_key, building) in buildings.iter() {
-            for craft in building.craft_list.iter() {
-                insert_craft_building(&mut region_crafts, craft, building);
-            }
-        }
-        crafts_summary.insert(region.to_string(), region_crafts);
+    pub(crate) fn matches(&self, craft: &CraftList) -> bool {
+        CRAFT_USAGE
+            .get(&craft.item_craft_index)
+            .map(|usage| {
+                normalize_name(usage).contains(&self.usage)
+                    && self.level.contains(craft.house_level)
+            })
+            .unwrap_or(false)
     }
-    Ok(crafts_summary)
 }
 
-fn filter_craft_list(craft_match: &str, building: &Building) -> Vec<CraftList> {
-    building
-        .craft_list
-        .clone()
-        .into_iter()
-        .filter(|craft| {
-            CRAFT_USAGE
-                .get(&craft.item_craft_index)
-                .map(|usage| format!("{} {}", usage, craft.house_level))
-                .unwrap()
-                .contains(craft_match)
-        })
-        .collect()
+/// A building offering a queried usage along with the CP needed to reach it from its town.
+#[derive(Clone, Debug)]
+pub(crate) struct CraftCandidate<'a> {
+    pub usage: String,
+    pub building: &'a Building,
+    pub prerequisites: Vec<&'a Building>,
+    pub total_cost: usize,
 }
 
-fn filter_craft_buildings(
-    regions_buildings: RegionBuildingMap,
-    craft_match: String,
-) -> Result<RegionBuildingMap> {
-    let mut filtered_regions = BTreeMap::<String, BuildingMap>::new();
-
-    for (region, buildings) in regions_buildings.iter() {
-        let mut filtered_buildings = BuildingMap::new();
-        for (key, building) in buildings.iter() {
-            let craft_list = filter_craft_list(&craft_match, building);
-            if !craft_list.is_empty() {
-                let mut new_building = building.clone();
-                new_building.craft_list = craft_list;
-                filtered_buildings.insert(*key, new_building);
+pub(crate) fn craft_candidates<'a>(
+    regions_buildings: &'a RegionBuildingMap,
+    query: &CraftQuery,
+) -> Vec<CraftCandidate<'a>> {
+    let mut candidates = vec![];
+    for buildings in regions_buildings.values() {
+        for building in buildings.values() {
+            for craft in building.craft_list.iter().filter(|c| query.matches(c)) {
+                let prerequisites = building_ancestors(building.key, buildings);
+                let total_cost =
+                    building.cost + prerequisites.iter().map(|b| b.cost).sum::<usize>();
+                candidates.push(CraftCandidate {
+                    usage: format!(
+                        "{} {}",
+                        CRAFT_USAGE.get(&craft.item_craft_index).unwrap(),
+                        craft.house_level
+                    ),
+                    building,
+                    prerequisites,
+                    total_cost,
+                });
             }
         }
-        if !filtered_buildings.is_empty() {
-            filtered_regions.insert(region.to_string(), filtered_buildings);
-        }
     }
-    Ok(filtered_regions)
+    candidates.sort_by(|a, b| {
+        (
+            a.total_cost,
+            a.building.cost,
+            &a.building.region_name,
+            a.building.key,
+        )
+            .cmp(&(
+                b.total_cost,
+                b.building.cost,
+                &b.building.region_name,
+                b.building.key,
+            ))
+    });
+    candidates
 }
 
+fn print_listing(candidates: &[CraftCandidate]) -> Result<()> {
+    let header_row = Row::from(vec![
+        Cell::new("Region").add_attribute(Attribute::Dim),
+        Cell::new("Crafting Usage").add_attribute(Attribute::Dim),
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("Cost").add_attribute(Attribute::Dim),
+        Cell::new("Prereqs").add_attribute(Attribute::Dim),
+        Cell::new("Total").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(header_row);
+
+    for candidate in candidates.iter() {
+        table.add_row(vec![
+            &candidate.building.region_name,
+            &candidate.usage,
+            &candidate.building.key.to_string(),
+            &candidate.building.building_name,
+            &candidate.building.cost.to_string(),
+            &candidate.prerequisites.len().to_string(),
+            &candidate.total_cost.to_string(),
+        ]);
+    }
+    println!("\n{table}");
+
+    Ok(())
+}
+
+/// Lists buildings offering a crafting usage ranked by the total CP needed to reach them.
 pub(crate) fn find_craft_buildings(region: Option<String>, craft: String) -> Result<()> {
-    let mut regions_buildings = parse_houseinfo_data()?;
-    if let Some(region) = region {
-        regions_buildings.retain(|k, _| *k == region);
+    let regions_buildings = get_region_buildings(region)?;
+    let query = CraftQuery::parse(&craft)?;
+    let candidates = craft_candidates(&regions_buildings, &query);
+    if candidates.is_empty() {
+        bail!(
+            "No buildings offer '{}'.\n Use '--list-crafts'.",
+            query.text
+        );
+    }
+    print_listing(&candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level(text: &str) -> LevelBound {
+        CraftQuery::parse(text).unwrap().level
+    }
+
+    #[test]
+    fn parse_usage_and_level_bounds() {
+        let query = CraftQuery::parse("  Refinery ").unwrap();
+        assert_eq!(query.usage, "refinery");
+        assert_eq!(query.text, "Refinery");
+        assert_eq!(query.level, LevelBound::Any);
+
+        assert_eq!(level("Refinery 2"), LevelBound::Exactly(2));
+        assert_eq!(level("Refinery=2"), LevelBound::Exactly(2));
+        assert_eq!(level("Refinery >= 2"), LevelBound::AtLeast(2));
+        assert_eq!(level("Refinery>2"), LevelBound::AtLeast(3));
+        assert_eq!(level("Refinery<=2"), LevelBound::AtMost(2));
+        assert_eq!(level("Refinery<2"), LevelBound::AtMost(1));
+        assert_eq!(level("Refinery>255"), LevelBound::AtLeast(256));
+        assert_eq!(
+            CraftQuery::parse("Wooden_Workbench").unwrap().usage,
+            "wooden workbench"
+        );
+    }
+
+    #[test]
+    fn parse_rejects_unsatisfiable_and_oversized_levels() {
+        assert!(CraftQuery::parse("Refinery<0").is_err());
+        assert!(CraftQuery::parse(&format!("Refinery>{}", u32::MAX)).is_err());
+        assert!(CraftQuery::parse(&format!("Refinery>={}", u32::MAX)).is_ok());
+        assert!(CraftQuery::parse("Refinery>99999999999").is_err());
+        assert!(CraftQuery::parse("Refinery 99999999999").is_err());
+    }
+
+    #[test]
+    fn parse_requires_a_usage() {
+        assert!(CraftQuery::parse("").is_err());
+        assert!(CraftQuery::parse(">=2").is_err());
+        assert!(CraftQuery::parse("   ").is_err());
+    }
+
+    #[test]
+    fn level_bounds_contain_levels() {
+        assert!(LevelBound::Any.contains(0));
+        assert!(LevelBound::Exactly(2).contains(2));
+        assert!(!LevelBound::Exactly(2).contains(3));
+        assert!(LevelBound::AtLeast(2).contains(5));
+        assert!(!LevelBound::AtLeast(2).contains(1));
+        assert!(LevelBound::AtMost(2).contains(0));
+        assert!(!LevelBound::AtMost(2).contains(3));
     }
-    let craft_buildings = filter_craft_buildings(regions_buildings, craft)?;
-    let crafts_summary = summarize_craft_buildings(craft_buildings)?;
-    print_listing(crafts_summary)
 }
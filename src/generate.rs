@@ -27,12 +27,13 @@
 //! ~200M/s single and ~1.425B/s for 15x threads.
 
 use std::cmp::min;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-use anyhow::{Ok, Result};
+use anyhow::{bail, Ok, Result};
 use log::Level::Debug;
 use rayon::prelude::*;
 use regex::Regex;
@@ -40,10 +41,14 @@ use serde::Serialize;
 use serde_json::to_string_pretty;
 use stable_vec::ExternStableVec;
 
-use crate::cli_args::Cli;
+use crate::cli_args::{Cli, TieBreak};
+use crate::estimate::{format_duration, RuntimeEstimate, BENCHMARK_MILLIS};
 use crate::houseinfo::*;
+use crate::list_buildings::Alternative;
 use crate::node_manipulation::{count_subtrees, count_subtrees_multistate};
 use crate::region_nodes::RegionNodes;
+use crate::scheduler::{check_region_results, for_each_region, thread_budget, RegionResults};
+use crate::selection::{chains_path, write_fingerprint, BuildingSelection};
 
 type ChainVec = Vec<Chain>;
 type ChainMapVec = Vec<ChainMap>;
@@ -58,6 +63,8 @@ struct Chain {
     cost: usize,
     indices: Vec<usize>,
     states: Vec<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<Alternative>,
 }
 
 impl Chain {
@@ -68,6 +75,7 @@ impl Chain {
             cost: region.usage_counts.cost,
             indices: (0..region.num_nodes).collect::<Vec<_>>(),
             states: region.states.clone(),
+            alternatives: vec![],
         };
         debug!("{:?}", chain);
         chain
@@ -110,6 +118,32 @@ impl Chain {
         }
     }
 
+    /// Steps to the next state that still rents every `forced` node.
+    ///
+    /// Removing a forced node would only lead to chains without it, so those states are skipped
+    /// by reducing further until a node that isn't forced is removed or changes state.
+    #[inline(always)]
+    fn next_forced_state(&mut self, region: &RegionNodes, forced: &[bool]) {
+        loop {
+            let index = match self.states.last() {
+                None => return,
+                Some(n) if n > &1 => self.reduce_last_state(region),
+                Some(_) => {
+                    let removed = *self.indices.last().unwrap();
+                    let index = self.reduce(region);
+                    if forced[removed] {
+                        continue;
+                    }
+                    index
+                }
+            };
+            if index < region.num_nodes {
+                self.extend(index, region);
+            }
+            return;
+        }
+    }
+
     #[inline(always)]
     fn reduce(&mut self, region: &RegionNodes) -> usize {
         self.states.pop();
@@ -142,6 +176,60 @@ impl Chain {
                 && self.worker_count == other.worker_count)
     }
 
+    /// Returns the `tiebreak` score of the chain, lower being preferred. `slot_counts[i][state]`
+    /// is the storage or lodging node `i` provides in `state`.
+    ///
+    /// Chains are only compared within a storage/lodging cell, where `Storage` always ties; an
+    /// equal-cost chain with less storage is already dropped by `retain_dominating`.
+    fn tiebreak_score(&self, tiebreak: TieBreak, slot_counts: &[[usize; 3]]) -> isize {
+        match tiebreak {
+            TieBreak::Buildings => self.indices.len() as isize,
+            TieBreak::PassThrough => self
+                .indices
+                .iter()
+                .zip(self.states.iter())
+                .skip(1)
+                .filter(|(i, state)| slot_counts[**i][**state] == 0)
+                .count() as isize,
+            TieBreak::Storage => -(self.warehouse_count as isize),
+        }
+    }
+
+    fn add_alternatives(&mut self, other: &Chain, tie_cap: usize) {
+        // Another chain's alternatives are ties too when merging parallel results.
+        let candidates = std::iter::once((&other.indices, &other.states)).chain(
+            other
+                .alternatives
+                .iter()
+                .map(|alternative| (&alternative.indices, &alternative.states)),
+        );
+        for (indices, states) in candidates {
+            if self.alternatives.len() >= tie_cap {
+                break;
+            }
+            let is_new = (&self.indices, &self.states) != (indices, states)
+                && !self
+                    .alternatives
+                    .iter()
+                    .any(|a| (&a.indices, &a.states) == (indices, states));
+            if is_new {
+                self.alternatives.push(Alternative {
+                    indices: indices.clone(),
+                    states: states.clone(),
+                });
+            }
+        }
+    }
+
+    #[inline(always)]
+    fn includes_all(&self, forced: &[bool]) -> bool {
+        // Chain indices are always in ascending pre-order.
+        forced
+            .iter()
+            .enumerate()
+            .all(|(i, &is_forced)| !is_forced || self.indices.binary_search(&i).is_ok())
+    }
+
     #[inline(always)]
     fn indices_difference_from_set(&self, set: &HashSet<usize>) -> Vec<usize> {
         set.difference(&self.indices.iter().copied().collect())
@@ -163,21 +251,48 @@ impl Chain {
 /// cost of some extra memory since a `n×n` matrix must be used.
 /// I thought using an `n×m` matrix and `x+y×stride` would be more efficient in terms of memory
 /// and time but in testing it was always a few percentage points slower.
+/// When `tie_cap` is non-zero up to that many equal-cost chains with different buildings or
+/// states are kept as alternatives of each incumbent. With a `tiebreak` an equal-cost chain
+/// replaces the incumbent when it scores lower, falling back to the indices and states so the
+/// result doesn't depend on the order chains are visited or merged in.
 #[derive(Clone, Debug)]
 struct ChainMap {
     cost: Vec<u16>,
     keys: ExternStableVec<usize>,
     chains: ExternStableVec<Chain>,
+    tie_cap: usize,
+    tiebreak: Option<TieBreak>,
+    slot_counts: Vec<[usize; 3]>,
 }
 
 impl ChainMap {
-    fn new(region: &RegionNodes) -> Self {
+    fn new(region: &RegionNodes, options: &GenerateOptions) -> Self {
         let len = (std::cmp::max(region.max_worker_count, region.max_warehouse_count) + 1).pow(2);
         let cost = vec![u16::MAX; len];
         let mut keys = ExternStableVec::<usize>::new();
         keys.reserve(len);
         let chains = ExternStableVec::<Chain>::new();
-        Self { cost, keys, chains }
+        let slot_counts = (0..region.num_nodes)
+            .map(|i| [0, region.warehouse_counts[i], region.worker_counts[i]])
+            .collect();
+        Self {
+            cost,
+            keys,
+            chains,
+            tie_cap: options.tie_cap,
+            tiebreak: options.tiebreak,
+            slot_counts,
+        }
+    }
+
+    #[inline(always)]
+    fn prefers(&self, chain: &Chain, incumbent: &Chain) -> bool {
+        let Some(tiebreak) = self.tiebreak else {
+            return false;
+        };
+        let score = |c: &Chain| c.tiebreak_score(tiebreak, &self.slot_counts);
+        (score(chain), &chain.indices, &chain.states)
+            < (score(incumbent), &incumbent.indices, &incumbent.states)
     }
 
     #[inline(always)]
@@ -194,6 +309,24 @@ impl ChainMap {
                 let entry = self.chains.get_unchecked_mut(*index);
                 chain.clone_into(entry);
             }
+        } else if self.cost[key] == chain.cost as u16
+            && (self.tie_cap > 0 || self.tiebreak.is_some())
+        {
+            let tie_cap = self.tie_cap;
+            unsafe {
+                let index = *self.keys.get_unchecked(key);
+                let replace = self.prefers(chain, self.chains.get_unchecked(index));
+                let entry = self.chains.get_unchecked_mut(index);
+                if replace {
+                    // The replaced incumbent is still an equal-cost alternative.
+                    let replaced = std::mem::replace(entry, chain.to_owned());
+                    if tie_cap > 0 {
+                        entry.add_alternatives(&replaced, tie_cap);
+                    }
+                } else if tie_cap > 0 {
+                    entry.add_alternatives(chain, tie_cap);
+                }
+            }
         }
     }
 
@@ -232,6 +365,57 @@ impl ChainMap {
     }
 }
 
+/// Chain requirements and recording options shared by the generating workers.
+#[derive(Clone, Debug, Default)]
+struct GenerateOptions {
+    /// Whether each node must be included in every chain; empty when no node is forced.
+    forced: Vec<bool>,
+    tie_cap: usize,
+    tiebreak: Option<TieBreak>,
+}
+
+impl GenerateOptions {
+    #[inline(always)]
+    fn accepts(&self, chain: &Chain) -> bool {
+        self.forced.is_empty() || chain.includes_all(&self.forced)
+    }
+
+    /// Steps `chain` to the next state the enumeration visits.
+    #[inline(always)]
+    fn next_state(&self, chain: &mut Chain, region: &RegionNodes) {
+        if self.forced.is_empty() {
+            chain.next_state(region);
+        } else {
+            chain.next_forced_state(region, &self.forced);
+        }
+    }
+
+    /// Returns the number of chains the enumeration visits for `region`.
+    fn combinations(&self, region: &RegionNodes) -> f64 {
+        if self.forced.is_empty() {
+            return count_subtrees_multistate(
+                region.root,
+                &region.parents,
+                &region.children,
+                &region.states,
+            );
+        }
+        // Subtree counts in reverse pre-order; a forced node's subtree can't be left out.
+        let mut counts = vec![1.0; region.num_nodes];
+        for i in (1..region.num_nodes).rev() {
+            counts[i] *= region.states[i] as f64;
+            let optional = if self.forced[i] { 0.0 } else { 1.0 };
+            let parent = region
+                .children
+                .iter()
+                .position(|&key| key == region.parents[i])
+                .unwrap();
+            counts[parent] *= counts[i] + optional;
+        }
+        counts[0]
+    }
+}
+
 #[derive(Clone, Debug)]
 struct JobControl {
     job_id: usize,
@@ -252,14 +436,21 @@ impl JobControl {
     /// workers and determining when the job should stop generating chain states so the jobs don't
     /// duplicate work. While this can under utilize hardware it works well for chunking the chain
     /// generation without needing to consider the full domain space.
-    fn many_from_region(cli: &Cli, region: &RegionNodes) -> Result<JobControlVec> {
+    ///
+    /// Jobs whose starting chain leaves out a forced node are dropped since every chain they
+    /// would visit leaves it out as well.
+    fn many_from_region(
+        cli: &Cli,
+        region: &RegionNodes,
+        options: &GenerateOptions,
+    ) -> Result<JobControlVec> {
         let mut prefix_chains = Self::prefixes(cli, region)?;
         let min_index = prefix_chains[0].indices.last().unwrap() + 1;
         let base_indices = (0..min_index).collect::<HashSet<_>>();
         let indices = (0..region.num_nodes).collect::<Vec<_>>();
 
         let mut job_controls = vec![];
-        for (job_id, chain) in prefix_chains.iter_mut().enumerate() {
+        for chain in prefix_chains.iter_mut() {
             let deactivated_nodes = chain.indices_difference_from_set(&base_indices);
             let stop_value = Self::stop_value(region, &deactivated_nodes, min_index);
             let stop_index = chain.indices.len();
@@ -284,8 +475,11 @@ impl JobControl {
                 }
             }
 
+            if !options.accepts(chain) {
+                continue;
+            }
             job_controls.push(JobControl {
-                job_id,
+                job_id: job_controls.len(),
                 chain: chain.clone(),
                 stop_index,
                 stop_value,
@@ -350,52 +544,193 @@ impl JobControl {
 pub(crate) fn generate(cli: &mut Cli) -> Result<()> {
     info!("preparing");
     let region_name = cli.region.clone().unwrap();
-    let forbidden_regions = ["Calpheon City", "Valencia City", "Heidel"];
-    if forbidden_regions.contains(&region_name.as_str()) {
-        let msg = format!(
-            "*** Generating exact results for {} will take years. ***\n \
-            ***    It is suggested you cancel this operation.    ***",
-            region_name
-        );
-        warn!("{}", msg);
-    }
+    let selection = BuildingSelection::from_cli(cli)?;
 
-    let mut do_all = false;
-    let all_region_buildings: RegionBuildingMap = if region_name == *"ALL" {
-        do_all = true;
+    let mut all_region_buildings: RegionBuildingMap = if region_name == *"ALL" {
         parse_houseinfo_data()?
     } else {
-        get_region_buildings(Some(region_name))?
+        get_region_buildings(Some(region_name.clone()))?
     };
 
-    for (region_name, region_buildings) in all_region_buildings.iter() {
-        if do_all && forbidden_regions.contains(&region_name.as_str()) {
-            continue;
+    // Estimated one region at a time, before any generating, so the benchmarks don't compete.
+    info!("estimating...");
+    let mut estimates = HashMap::new();
+    let mut failed: RegionResults<()> = vec![];
+    let shared = all_region_buildings.len() > 1;
+    for (name, buildings) in all_region_buildings.iter() {
+        // A region the selection can't apply to is reported with the others' failures.
+        match prepare_region(cli, buildings, &selection) {
+            Result::Ok((region, options)) => {
+                let estimate = estimate_region(cli, &region, &options, shared);
+                estimates.insert(name.clone(), estimate);
+            }
+            Err(e) => {
+                error!("{}: {:#}", name, e);
+                failed.push((name.clone(), Err(e)));
+            }
+        }
+    }
+    all_region_buildings.retain(|name, _| estimates.contains_key(name));
+    if !cli.force {
+        let over: Vec<(&String, &RuntimeEstimate)> = all_region_buildings
+            .keys()
+            .map(|name| (name, &estimates[name]))
+            .filter(|(_, estimate)| estimate.seconds > cli.max_estimated_time)
+            .collect();
+        if region_name != *"ALL" {
+            if let Some((name, estimate)) = over.first() {
+                bail!(
+                    "Generating {} is estimated to take {}, over the --max-estimated-time of {}. \
+                    Use --optimize for this region or --force to generate it anyway.",
+                    name,
+                    estimate,
+                    format_duration(cli.max_estimated_time)
+                );
+            }
         }
-        cli.region = Some(region_name.to_owned());
-        let region = RegionNodes::new(region_buildings)?;
-
-        if !cli.verbose.is_silent() {
-            trace!("Buildings");
-            region_buildings.iter().for_each(|b| trace!("{:#?}", b));
-            print_region_specs(&region);
-            print_starting_status(&region);
+        for (name, estimate) in over.iter().filter(|_| !cli.verbose.is_silent()) {
+            println!(
+                "Skipping {}: estimated to take {}, over the --max-estimated-time of {}.",
+                name,
+                estimate,
+                format_duration(cli.max_estimated_time)
+            );
         }
+        let over: Vec<String> = over.into_iter().map(|(name, _)| name.clone()).collect();
+        all_region_buildings.retain(|name, _| !over.contains(name));
+    }
+
+    let cli: &Cli = cli;
+    let mut results = for_each_region(cli, &all_region_buildings, |region_name, buildings| {
+        generate_region(
+            cli,
+            region_name,
+            buildings,
+            &selection,
+            &estimates[region_name],
+        )
+    });
+    results.extend(failed);
+    check_region_results(&results)
+}
 
-        if region.num_nodes < 20 {
-            cli.jobs = Some(1);
+/// Returns the region's nodes after the building selection with the matching generator options.
+fn prepare_region(
+    cli: &Cli,
+    region_buildings: &BuildingMap,
+    selection: &BuildingSelection,
+) -> Result<(RegionNodes, GenerateOptions)> {
+    let region_selection = selection.for_region(region_buildings)?;
+    let region_buildings = &region_selection.retain_buildings(region_buildings)?;
+    let region = RegionNodes::new(region_buildings)?;
+    let options = GenerateOptions {
+        forced: if region_selection.included.is_empty() {
+            vec![]
+        } else {
+            region
+                .children
+                .iter()
+                .map(|key| region_selection.included.contains(key))
+                .collect()
+        },
+        tie_cap: cli.tie_cap(),
+        tiebreak: cli.tiebreak,
+    };
+    Ok((region, options))
+}
+
+/// Returns the generator job count used for `region`, the `--jobs` thread budget; small
+/// regions aren't worth splitting.
+fn region_jobs(cli: &Cli, region: &RegionNodes) -> usize {
+    if region.num_nodes < 20 {
+        1
+    } else {
+        thread_budget(cli).min(u8::MAX as usize)
+    }
+}
+
+/// Runs the region's generator for up to `BENCHMARK_MILLIS` to estimate its full run time.
+///
+/// Regions that can't go over `--max-estimated-time`, and every region with `--force`, are only
+/// bounded at `MIN_RATE` to save the benchmark.
+///
+/// `shared` is set when other regions run on the same thread pool; the estimate still assumes
+/// the region has every thread to itself.
+fn estimate_region(
+    cli: &Cli,
+    region: &RegionNodes,
+    options: &GenerateOptions,
+    shared: bool,
+) -> RuntimeEstimate {
+    let combinations = options.combinations(region);
+    let threads = region_jobs(cli, region);
+    let bound = RuntimeEstimate::unmeasured(combinations, threads, shared);
+    if cli.force || bound.seconds <= cli.max_estimated_time {
+        debug!("{} estimate: {:?}", region.region_name, bound);
+        return bound;
+    }
+    let limit = Duration::from_millis(BENCHMARK_MILLIS);
+    let start = Instant::now();
+    let mut chain = Chain::new(region);
+    let mut chains = ChainMap::new(region, options);
+    let mut counter: usize = 0;
+
+    while !chain.indices.is_empty() {
+        chains.insert_or_update(&chain);
+        options.next_state(&mut chain, region);
+        counter += 1;
+        if counter.is_multiple_of(4096) && start.elapsed() >= limit {
+            break;
         }
+    }
 
-        info!("generating...");
-        let chains = match cli.jobs.unwrap_or(1) {
-            1 => generate_dominating(&region)?,
-            _ => generate_dominating_par(cli, &region)?,
-        };
-        info!("retaining...");
-        let mut chains = chains.retain_dominating_to_vec();
-        info!("writing...");
-        write_chains(cli, &region, &mut chains)?;
+    let estimate = RuntimeEstimate::new(
+        combinations,
+        counter,
+        start.elapsed().as_secs_f64(),
+        threads,
+        shared,
+    );
+    debug!("{} estimate: {:?}", region.region_name, estimate);
+    estimate
+}
+
+fn generate_region(
+    cli: &Cli,
+    region_name: &str,
+    region_buildings: &BuildingMap,
+    selection: &BuildingSelection,
+    estimate: &RuntimeEstimate,
+) -> Result<()> {
+    let mut cli = cli.clone();
+    cli.region = Some(region_name.to_owned());
+    let (region, options) = prepare_region(&cli, region_buildings, selection)?;
+
+    if !cli.verbose.is_silent() {
+        trace!("Buildings");
+        region.buildings.iter().for_each(|b| trace!("{:#?}", b));
+        print_region_specs(&region);
+        print_starting_status(&region, estimate);
     }
+
+    cli.jobs = Some(region_jobs(&cli, &region) as u8);
+
+    info!("generating {}...", region_name);
+    let chains = match cli.jobs.unwrap_or(1) {
+        1 => generate_dominating(&region, &options)?,
+        _ => generate_dominating_par(&cli, &region, &options)?,
+    };
+    info!("retaining {}...", region_name);
+    let mut chains = chains.retain_dominating_to_vec();
+    info!("writing {}...", region_name);
+    let region_selection = selection.for_region(region_buildings)?;
+    write_chains(
+        &cli,
+        &region,
+        region_buildings,
+        &mut chains,
+        &region_selection,
+    )?;
     Ok(())
 }
 
@@ -421,14 +756,14 @@ fn generate_all(region: &RegionNodes) -> Result<ChainVec> {
 }
 
 #[inline(always)]
-fn generate_dominating(region: &RegionNodes) -> Result<ChainMap> {
+fn generate_dominating(region: &RegionNodes, options: &GenerateOptions) -> Result<ChainMap> {
     let mut chain = Chain::new(region);
-    let mut chains = ChainMap::new(region);
+    let mut chains = ChainMap::new(region, options);
     let mut counter: usize = 0;
 
     while !chain.indices.is_empty() {
         chains.insert_or_update(&chain);
-        chain.next_state(region);
+        options.next_state(&mut chain, region);
         counter += 1;
     }
 
@@ -440,11 +775,17 @@ fn generate_dominating(region: &RegionNodes) -> Result<ChainMap> {
     Ok(chains)
 }
 
-fn generate_dominating_par(cli: &Cli, region: &RegionNodes) -> Result<ChainMap> {
-    let job_controls = JobControl::many_from_region(cli, region)?;
+fn generate_dominating_par(
+    cli: &Cli,
+    region: &RegionNodes,
+    options: &GenerateOptions,
+) -> Result<ChainMap> {
+    let job_controls = JobControl::many_from_region(cli, region, options)?;
     let mut results = job_controls
         .into_par_iter()
-        .map(|job| generate_dominating_par_worker(cli.clone(), region.clone(), job).unwrap())
+        .map(|job| {
+            generate_dominating_par_worker(cli.clone(), region.clone(), job, options).unwrap()
+        })
         .collect::<ChainMapVec>();
     info!("merging...");
     let results = ChainMap::flatten_many_by_insert_update(&mut results);
@@ -457,18 +798,21 @@ fn generate_dominating_par_worker(
     _cli: Cli,
     region: RegionNodes,
     job: JobControl,
+    options: &GenerateOptions,
 ) -> Result<ChainMap> {
-    let mut chains = ChainMap::new(&region);
+    let mut chains = ChainMap::new(&region, options);
     let mut chain = job.chain;
     let mut counter: usize = 0;
 
     while chain.indices.len() > job.stop_index && chain.indices[job.stop_index] >= job.stop_value {
         chains.insert_or_update(&chain);
-        chain.next_state(&region);
+        options.next_state(&mut chain, &region);
         counter += 1;
     }
     counter += 1;
-    chains.insert_or_update(&chain);
+    if !chain.indices.is_empty() {
+        chains.insert_or_update(&chain);
+    }
 
     info!(
         "Generate Dominating worker {} visited {} combinations yielding {:?} chains.",
@@ -500,7 +844,7 @@ fn print_region_specs(region: &RegionNodes) {
     );
 }
 
-fn print_starting_status(region: &RegionNodes) {
+fn print_starting_status(region: &RegionNodes, estimate: &RuntimeEstimate) {
     let building_chain_count = count_subtrees(region.root, &region.parents, &region.children);
     let multistate_count = count_subtrees_multistate(
         region.root,
@@ -508,12 +852,15 @@ fn print_starting_status(region: &RegionNodes) {
         &region.children,
         &region.states,
     );
+    // One write per region keeps the lines together when regions run in parallel.
+    let name = &region.region_name;
     println!(
-        "Generating values for {} consisting of {} buildings in {} chains with {} storage/lodging combinations",
-        region.region_name, region.buildings.len(), building_chain_count, multistate_count
-    );
-    println!(
-        "With a maximum cost of {} with {} lodging and {} storage (out of {:?} possible).",
+        "Generating values for {name} consisting of {} buildings in {} chains with {} storage/lodging combinations\n\
+        {name}: With a maximum cost of {} with {} lodging and {} storage (out of {:?} possible).\n\
+        {name}: Estimated generation time: {estimate}.",
+        region.buildings.len(),
+        building_chain_count,
+        multistate_count,
         region.usage_counts.cost,
         region.usage_counts.worker_count,
         region.usage_counts.warehouse_count,
@@ -521,28 +868,41 @@ fn print_starting_status(region: &RegionNodes) {
     );
 }
 
-fn write_chains(cli: &Cli, region: &RegionNodes, chains: &mut Vec<Chain>) -> Result<()> {
+fn write_chains(
+    cli: &Cli,
+    region: &RegionNodes,
+    region_buildings: &BuildingMap,
+    chains: &mut Vec<Chain>,
+    selection: &BuildingSelection,
+) -> Result<()> {
     if cli.for_validation {
         for chain in chains.iter_mut() {
             chain.indices.clear();
             chain.states.clear();
+            chain.alternatives.clear();
         }
     } else {
         for chain in chains.iter_mut() {
             chain.indices = chain.indices.iter().map(|j| region.children[*j]).collect();
+            for alternative in chain.alternatives.iter_mut() {
+                alternative.indices = alternative
+                    .indices
+                    .iter()
+                    .map(|j| region.children[*j])
+                    .collect();
+            }
         }
     }
     let region_name = cli.region.clone().unwrap();
-    let file_name = region_name.replace(' ', "_");
     let path = if cli.for_validation {
-        format!(
+        let file_name = region_name.replace(' ', "_");
+        PathBuf::from(format!(
             "./data/housecraft/validation/popjumppush/{}.json",
             file_name
-        )
+        ))
     } else {
-        format!("./data/housecraft/{}.json", file_name)
+        chains_path(&region_name, selection)
     };
-    let path = PathBuf::from(path);
     fs::create_dir_all(path.parent().unwrap())?;
     let mut output = File::create(path.clone())?;
 
@@ -554,6 +914,9 @@ fn write_chains(cli: &Cli, region: &RegionNodes, chains: &mut Vec<Chain>) -> Res
         })
         .to_string();
     output.write_all(json.as_bytes())?;
+    if !cli.for_validation {
+        write_fingerprint(&path, region_buildings)?;
+    }
 
     if !cli.verbose.is_silent() {
         println!(
@@ -565,3 +928,112 @@ fn write_chains(cli: &Cli, region: &RegionNodes, chains: &mut Vec<Chain>) -> Res
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_manipulation::generate_jump_indices;
+
+    /// A region of keys 1..=6 under root 100: 1 -> {2, 3}, 4 -> {5} and 6.
+    fn region() -> RegionNodes {
+        let parents = vec![0, 100, 1, 1, 100, 4, 100];
+        let children = vec![100, 1, 2, 3, 4, 5, 6];
+        let jump_indices = generate_jump_indices(&parents, &children);
+        RegionNodes {
+            region_name: "Test".to_string(),
+            buildings: BuildingMap::new(),
+            max_warehouse_count: 15,
+            max_worker_count: 6,
+            num_nodes: 7,
+            root: 100,
+            parents,
+            children,
+            costs: vec![0, 1, 2, 3, 4, 5, 6],
+            states: vec![0, 2, 1, 2, 1, 2, 1],
+            warehouse_counts: vec![0, 1, 2, 3, 4, 5, 0],
+            worker_counts: vec![0, 1, 0, 2, 0, 3, 0],
+            jump_indices,
+            usage_counts: UsageCounters {
+                cost: 21,
+                warehouse_count: 6,
+                worker_count: 6,
+            },
+        }
+    }
+
+    fn options(forced: &[usize]) -> GenerateOptions {
+        GenerateOptions {
+            forced: if forced.is_empty() {
+                vec![]
+            } else {
+                (0..7).map(|i| forced.contains(&i)).collect()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn visited(region: &RegionNodes, options: &GenerateOptions) -> Vec<Chain> {
+        let mut chain = Chain::new(region);
+        let mut chains = vec![];
+        while !chain.indices.is_empty() {
+            chains.push(chain.clone());
+            options.next_state(&mut chain, region);
+        }
+        chains
+    }
+
+    fn totals(region: &RegionNodes, chain: &Chain) -> (usize, usize, usize) {
+        let mut totals = (0, 0, 0);
+        for (&i, &state) in chain.indices.iter().zip(chain.states.iter()).skip(1) {
+            totals.0 += region.costs[i];
+            match state {
+                1 => totals.1 += region.warehouse_counts[i],
+                _ => totals.2 += region.worker_counts[i],
+            }
+        }
+        totals
+    }
+
+    #[test]
+    fn enumeration_visits_every_combination_once() {
+        let region = region();
+        let options = options(&[]);
+        let chains = visited(&region, &options);
+        assert_eq!(chains.len() as f64, options.combinations(&region));
+        let unique: HashSet<_> = chains.iter().map(|c| (&c.indices, &c.states)).collect();
+        assert_eq!(unique.len(), chains.len());
+        for chain in &chains {
+            assert_eq!(
+                (chain.cost, chain.warehouse_count, chain.worker_count),
+                totals(&region, chain)
+            );
+        }
+    }
+
+    #[test]
+    fn forced_enumeration_skips_only_chains_without_forced_nodes() {
+        let region = region();
+        let all = visited(&region, &options(&[]));
+        for forced in [vec![1], vec![1, 3], vec![4, 5], vec![6], vec![1, 2, 4, 6]] {
+            let options = options(&forced);
+            let expected: Vec<_> = all
+                .iter()
+                .filter(|c| c.includes_all(&options.forced))
+                .map(|c| (c.indices.clone(), c.states.clone()))
+                .collect();
+            let chains = visited(&region, &options);
+            let pruned: Vec<_> = chains
+                .iter()
+                .map(|c| (c.indices.clone(), c.states.clone()))
+                .collect();
+            assert_eq!(pruned, expected, "forced {forced:?}");
+            assert_eq!(chains.len() as f64, options.combinations(&region));
+            for chain in &chains {
+                assert_eq!(
+                    (chain.cost, chain.warehouse_count, chain.worker_count),
+                    totals(&region, chain)
+                );
+            }
+        }
+    }
+}
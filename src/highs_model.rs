@@ -0,0 +1,124 @@
+//! A HiGHS model handle shared by the storage/lodging optimizer and the crafting planner.
+//!
+//! Owns the `highs_ptr` and wraps the option, column, row and solution calls both models make
+//! so their HiGHS setup can't drift apart.
+
+use std::ffi::{c_void, CString};
+use std::ptr::null;
+
+use highs_sys::*;
+
+pub(crate) struct HighsModel {
+    highs_ptr: *mut c_void,
+}
+
+impl HighsModel {
+    /// Creates an empty model logging (at trace level) to `{log_name}_highs.log` when given.
+    pub fn new(log_name: Option<&str>) -> Self {
+        let mut model = HighsModel {
+            highs_ptr: unsafe { Highs_create() },
+        };
+        let do_logging = log_enabled!(log::Level::Trace);
+        model.set_bool_option("output_flag", do_logging);
+        model.set_bool_option("log_to_console", do_logging);
+        if let Some(log_name) = log_name.filter(|_| do_logging) {
+            model.set_string_option("log_file", &format!("{log_name}_highs.log"));
+        }
+        model
+    }
+
+    pub fn mut_ptr(&mut self) -> *mut c_void {
+        self.highs_ptr
+    }
+
+    pub fn infinity(&self) -> f64 {
+        unsafe { Highs_getInfinity(self.highs_ptr) }
+    }
+
+    pub fn num_rows(&self) -> HighsInt {
+        unsafe { Highs_getNumRows(self.highs_ptr) }
+    }
+
+    pub fn set_bool_option(&mut self, option: &str, value: bool) {
+        let option = CString::new(option).unwrap();
+        unsafe { Highs_setBoolOptionValue(self.highs_ptr, option.as_ptr(), value as HighsInt) };
+    }
+
+    pub fn set_int_option(&mut self, option: &str, value: HighsInt) {
+        let option = CString::new(option).unwrap();
+        unsafe { Highs_setIntOptionValue(self.highs_ptr, option.as_ptr(), value) };
+    }
+
+    pub fn set_double_option(&mut self, option: &str, value: f64) {
+        let option = CString::new(option).unwrap();
+        unsafe { Highs_setDoubleOptionValue(self.highs_ptr, option.as_ptr(), value) };
+    }
+
+    pub fn set_string_option(&mut self, option: &str, value: &str) {
+        let option = CString::new(option).unwrap();
+        let value = CString::new(value).unwrap();
+        unsafe { Highs_setStringOptionValue(self.highs_ptr, option.as_ptr(), value.as_ptr()) };
+    }
+
+    /// Adds a binary column with objective `cost` and returns its column id.
+    pub fn add_binary_col(&mut self, cost: f64) -> HighsInt {
+        unsafe {
+            let column_id = Highs_getNumCols(self.highs_ptr);
+            Highs_addCol(self.highs_ptr, cost, 0.0, 1.0, 0, null(), null());
+            Highs_changeColIntegrality(self.highs_ptr, column_id, kHighsVarTypeInteger);
+            column_id
+        }
+    }
+
+    /// Adds the row `lower <= sum(avalue[i] * col[aindex[i]]) <= upper`.
+    pub fn add_row(&mut self, lower: f64, upper: f64, aindex: &[HighsInt], avalue: &[f64]) {
+        unsafe {
+            Highs_addRow(
+                self.highs_ptr,
+                lower,
+                upper,
+                aindex.len() as HighsInt,
+                aindex.as_ptr(),
+                avalue.as_ptr(),
+            );
+        }
+    }
+
+    /// Runs the solver and returns the model status.
+    pub fn run(&mut self) -> HighsInt {
+        unsafe {
+            Highs_run(self.highs_ptr);
+            Highs_getModelStatus(self.highs_ptr)
+        }
+    }
+
+    /// Returns the solution's column values rounded to integers.
+    pub fn solution_col_values(&self) -> Vec<u32> {
+        let num_cols = unsafe { Highs_getNumCols(self.highs_ptr) };
+        let num_rows = self.num_rows();
+        let mut col_value: Vec<f64> = vec![0.; num_cols as usize];
+        let mut col_dual: Vec<f64> = vec![0.; num_cols as usize];
+        let mut row_value: Vec<f64> = vec![0.; num_rows as usize];
+        let mut row_dual: Vec<f64> = vec![0.; num_rows as usize];
+        unsafe {
+            Highs_getSolution(
+                self.highs_ptr,
+                col_value.as_mut_ptr(),
+                col_dual.as_mut_ptr(),
+                row_value.as_mut_ptr(),
+                row_dual.as_mut_ptr(),
+            );
+        };
+        let col_value: Vec<_> = col_value.iter().map(|v| v.round() as u32).collect();
+        trace!("Solution values:\n\t{:?}", col_value);
+        col_value
+    }
+}
+
+impl Drop for HighsModel {
+    fn drop(&mut self) {
+        unsafe {
+            Highs_destroy(self.highs_ptr);
+        }
+    }
+}
@@ -1,17 +1,23 @@
-use anyhow::{bail, Context, Ok, Result};
+use anyhow::{Context, Ok, Result};
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::path::Path;
 
+use crate::name_resolution::resolve_region_name;
+
 pub(crate) type IndexedStringMap = HashMap<usize, String>;
 pub(crate) type BuildingMap = BTreeMap<usize, Building>;
 pub(crate) type RegionBuildingMap = BTreeMap<String, BuildingMap>;
-pub(crate) type CraftBuildingMap = BTreeMap<String, (usize, Vec<Building>)>;
 
 // pub const HOUSECRAFT_TABLE_STYLE: &str = "0123456789abcdefghi";
 pub(crate) const HOUSECRAFT_TABLE_STYLE: &str = "   ═────      ═  ══";
 
+lazy_static! {
+    pub(crate) static ref CRAFT_USAGE: IndexedStringMap =
+        read_csv_data("HouseInfoReceipe.csv").expect("Error reading HouseInfoReceipe.csv");
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct UsageCounters {
     pub cost: usize,
@@ -210,13 +216,39 @@ pub(crate) fn parse_houseinfo_data() -> Result<RegionBuildingMap> {
 pub(crate) fn get_region_buildings(region_name: Option<String>) -> Result<RegionBuildingMap> {
     let mut region_buildings = parse_houseinfo_data()?;
     if let Some(region_name) = region_name {
+        let region_name = resolve_region_name(&region_buildings, &region_name)?;
         region_buildings.retain(|k, _| *k == region_name);
-        if region_buildings.len() != 1 {
-            bail!(
-                "Unable to find region {}.\n Use '--list-regions'.",
-                region_name
-            );
-        }
     };
     Ok(region_buildings)
 }
+
+pub(crate) fn building_ancestors(key: usize, buildings: &BuildingMap) -> Vec<&Building> {
+    /*!  - Returns the buildings required before renting `key`, nearest first. */
+    let mut ancestors = vec![];
+    let mut building = buildings.get(&key);
+    while let Some(parent) = building.and_then(|b| buildings.get(&b.need_key)) {
+        if parent.key == key || ancestors.len() > buildings.len() {
+            break;
+        }
+        ancestors.push(parent);
+        building = Some(parent);
+    }
+    ancestors
+}
+
+pub(crate) fn building_dependents(key: usize, buildings: &BuildingMap) -> Vec<(usize, &Building)> {
+    /*!  - Returns (depth, building) for every building requiring `key`, in traversal pre-order. */
+    let mut dependents = vec![];
+    let mut stack: Vec<(usize, usize)> = vec![(0, key)];
+    while let Some((depth, parent)) = stack.pop() {
+        if depth > 0 {
+            dependents.push((depth, buildings.get(&parent).unwrap()));
+        }
+        for child in buildings.values().rev().filter(|b| b.need_key == parent) {
+            if child.key != parent && depth < buildings.len() {
+                stack.push((depth + 1, child.key));
+            }
+        }
+    }
+    dependents
+}
@@ -1,43 +1,129 @@
-use std::fs::File;
+use std::fmt;
+use std::fs::{self, File};
 use std::io::BufReader;
 use std::path::PathBuf;
-use std::{fmt, process};
 
 use anyhow::{bail, Context, Ok, Result};
 use comfy_table::{Attribute, Cell, Table};
+use console::style;
 use once_cell::sync::OnceCell;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::cli_args::Cli;
+use crate::explain::print_explanation;
 use crate::houseinfo::*;
+use crate::optimize::{optimize_point, optimize_within};
 use crate::region_nodes::RegionNodes;
+use crate::selection::{chains_path, fingerprint_matches, write_fingerprint, BuildingSelection};
 
 static REGION: OnceCell<RegionNodes> = OnceCell::new();
 
-type ChainVec = Vec<Chain>;
+pub(crate) type ChainVec = Vec<Chain>;
 
-#[derive(Debug, Deserialize, Eq, PartialEq)]
-struct Chain {
-    lodging: u16,
-    storage: u16,
-    cost: u16,
-    indices: Vec<usize>,
-    states: Vec<usize>,
+/// A different building/state set with the same cost, storage and lodging as its chain.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct Alternative {
+    pub indices: Vec<usize>,
+    pub states: Vec<usize>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub(crate) struct Chain {
+    pub lodging: u16,
+    pub storage: u16,
+    pub cost: u16,
+    pub indices: Vec<usize>,
+    pub states: Vec<usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub alternatives: Vec<Alternative>,
 }
 
 impl Chain {
-    fn many_from_region_json(region_name: &str) -> Result<ChainVec> {
-        let filename = format!("{}.json", region_name.replace(' ', "_"));
-        let path = PathBuf::from("./data/housecraft").join(filename);
-        let file = File::open(&path).with_context(|| format!("Can't find {}", path.display()))?;
+    pub(crate) fn many_from_region_json(region_name: &str) -> Result<ChainVec> {
+        Self::many_from_selection_json(region_name, &BuildingSelection::default())
+    }
+
+    /// Returns the chains generated for `region_name` with the region `selection`.
+    pub(crate) fn many_from_selection_json(
+        region_name: &str,
+        selection: &BuildingSelection,
+    ) -> Result<ChainVec> {
+        let path = chains_path(region_name, selection);
+        let file = File::open(&path).with_context(|| match selection.is_empty() {
+            true => format!("Can't find {}", path.display()),
+            false => format!(
+                "Can't find {}.\n Use '--generate' or '--optimize' with the same \
+                '--exclude'/'--include' to create it.",
+                path.display()
+            ),
+        })?;
         let reader = BufReader::new(file);
         let chains = serde_json::from_reader(reader)?;
         Ok(chains)
     }
 }
 
-impl fmt::Display for Chain {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// A live solved chain along with the minimums it was solved for.
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedChain {
+    query_storage: u16,
+    query_lodging: u16,
+    #[serde(flatten)]
+    chain: Chain,
+}
+
+impl CachedChain {
+    fn path(region_name: &str, selection: &BuildingSelection) -> PathBuf {
+        PathBuf::from("./data/housecraft/cache").join(selection.file_name(region_name))
+    }
+
+    fn many_from_cache_json(region_name: &str, selection: &BuildingSelection) -> Vec<CachedChain> {
+        let path = Self::path(region_name, selection);
+        if !fingerprint_matches(&path, &REGION.get().unwrap().buildings) {
+            return vec![];
+        }
+        File::open(path)
+            .ok()
+            .and_then(|file| serde_json::from_reader(BufReader::new(file)).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_many(
+        region_name: &str,
+        selection: &BuildingSelection,
+        cached: &[CachedChain],
+    ) -> Result<()> {
+        let path = Self::path(region_name, selection);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(&path, serde_json::to_string_pretty(cached)?)?;
+        write_fingerprint(&path, &REGION.get().unwrap().buildings)
+    }
+
+    fn answers(&self, storage: u16, lodging: u16) -> bool {
+        // The cheapest chain for looser minimums is also the cheapest for any tighter minimums
+        // it still satisfies.
+        self.query_storage <= storage
+            && self.query_lodging <= lodging
+            && self.chain.storage >= storage
+            && self.chain.lodging >= lodging
+    }
+}
+
+/// Returns the region's chains made with `selection` when the file exists and was written from
+/// the region's current buildings.
+fn current_chains(region: &RegionNodes, selection: &BuildingSelection) -> Option<ChainVec> {
+    let chains = Chain::many_from_selection_json(&region.region_name, selection).ok()?;
+    let path = chains_path(&region.region_name, selection);
+    if fingerprint_matches(&path, &region.buildings) {
+        Some(chains)
+    } else {
+        info!("{} chains don't match houseinfo.json", region.region_name);
+        None
+    }
+}
+
+impl Chain {
+    fn table(&self, indices: &[usize], states: &[usize]) -> Table {
         let region = REGION.get().unwrap();
 
         let mut table = Table::new();
@@ -50,7 +136,7 @@ impl fmt::Display for Chain {
             Cell::new("L").add_attribute(Attribute::Dim),
         ]);
 
-        for (index, state) in self.indices.iter().zip(self.states.iter()).skip(1) {
+        for (index, state) in indices.iter().zip(states.iter()).skip(1) {
             let building = &region.buildings.get(index).unwrap();
             let (warehouse_count, worker_count) = if *state == 1 {
                 (building.warehouse_count.to_string(), "".to_string())
@@ -73,52 +159,479 @@ impl fmt::Display for Chain {
             Cell::new(self.storage).add_attribute(Attribute::Bold),
             Cell::new(self.lodging).add_attribute(Attribute::Bold),
         ]);
+        table
+    }
+
+    /// Returns the chain's (key, state) pairs in rental order.
+    ///
+    /// Each step takes, among the buildings whose prerequisite is already rented, the one with
+    /// the most storage or lodging slots per CP; ties go to the earlier building in pre-order.
+    fn rental_order(&self) -> Vec<(usize, usize)> {
+        let region = REGION.get().unwrap();
+        let mut pending: Vec<(usize, usize)> = self
+            .indices
+            .iter()
+            .copied()
+            .zip(self.states.iter().copied())
+            .skip(1)
+            .collect();
+        let mut order = vec![];
+        while !pending.is_empty() {
+            let is_ready = |key: &usize| {
+                let need_key = region.buildings[key].need_key;
+                !pending.iter().any(|(k, _)| *k == need_key)
+            };
+            let value_per_cost = |(key, state): &(usize, usize)| {
+                let building = &region.buildings[key];
+                let value = if *state == 1 {
+                    building.warehouse_count
+                } else {
+                    building.worker_count
+                };
+                value as f64 / building.cost.max(1) as f64
+            };
+            let (next, _) = pending
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| is_ready(&entry.0))
+                .fold(None, |best: Option<(usize, f64)>, (i, entry)| {
+                    let value = value_per_cost(entry);
+                    match best {
+                        Some((_, best_value)) if best_value >= value => best,
+                        _ => Some((i, value)),
+                    }
+                })
+                .unwrap();
+            order.push(pending.remove(next));
+        }
+        order
+    }
+
+    /// Returns the chain's rental order with cumulative CP, storage and lodging.
+    pub(crate) fn schedule_table(&self) -> Table {
+        let region = REGION.get().unwrap();
+
+        let mut table = Table::new();
+        table.load_preset(HOUSECRAFT_TABLE_STYLE);
+        table.set_header(vec![
+            Cell::new("Step").add_attribute(Attribute::Dim),
+            Cell::new("Key").add_attribute(Attribute::Dim),
+            Cell::new("Building").add_attribute(Attribute::Dim),
+            Cell::new("C").add_attribute(Attribute::Dim),
+            Cell::new("S").add_attribute(Attribute::Dim),
+            Cell::new("L").add_attribute(Attribute::Dim),
+            Cell::new("Σ C").add_attribute(Attribute::Dim),
+            Cell::new("Σ S").add_attribute(Attribute::Dim),
+            Cell::new("Σ L").add_attribute(Attribute::Dim),
+        ]);
+
+        let mut counts = UsageCounters::new();
+        for (step, (key, state)) in self.rental_order().iter().enumerate() {
+            let building = &region.buildings[key];
+            counts.cost += building.cost;
+            let (warehouse_count, worker_count) = if *state == 1 {
+                counts.warehouse_count += building.warehouse_count;
+                (building.warehouse_count.to_string(), "".to_string())
+            } else {
+                counts.worker_count += building.worker_count;
+                ("".to_string(), building.worker_count.to_string())
+            };
+            table.add_row(vec![
+                &(step + 1).to_string(),
+                &building.key.to_string(),
+                &building.building_name,
+                &building.cost.to_string(),
+                &warehouse_count,
+                &worker_count,
+                &counts.cost.to_string(),
+                &counts.warehouse_count.to_string(),
+                &counts.worker_count.to_string(),
+            ]);
+        }
+
+        table.add_row(vec![
+            Cell::new("Totals").add_attribute(Attribute::Bold),
+            Cell::new("").add_attribute(Attribute::Bold),
+            Cell::new("").add_attribute(Attribute::Bold),
+            Cell::new(self.cost).add_attribute(Attribute::Bold),
+            Cell::new(self.storage).add_attribute(Attribute::Bold),
+            Cell::new(self.lodging).add_attribute(Attribute::Bold),
+            Cell::new("").add_attribute(Attribute::Bold),
+            Cell::new("").add_attribute(Attribute::Bold),
+            Cell::new("").add_attribute(Attribute::Bold),
+        ]);
+        table
+    }
+}
+
+fn print_chain(cli: &Cli, chain: &Chain) {
+    if cli.schedule {
+        println!("{}\n", chain.schedule_table());
+    } else {
+        println!("{chain}");
+    }
+}
 
-        writeln!(f, "{table}")?;
+impl fmt::Display for Chain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.table(&self.indices, &self.states))?;
+        for (i, alternative) in self.alternatives.iter().enumerate() {
+            let label = format!("Alternative {} of {}", i + 1, self.alternatives.len());
+            writeln!(f, "{}", style(label).dim())?;
+            writeln!(
+                f,
+                "{}",
+                self.table(&alternative.indices, &alternative.states)
+            )?;
+        }
         std::result::Result::Ok(())
     }
 }
 
-fn filter_by_storage_and_lodging(chains: &mut ChainVec, cli: Cli) {
-    if cli.storage.is_none() && cli.lodging.is_none() {
-        return;
+/// The error for storage and lodging minimums no chain of a region provides.
+///
+/// Along with the region's maxima it carries the storage/lodging trade-off boundary (the most
+/// storage possible at each lodging count) and the achievable front points closest to the
+/// request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct InfeasibleQuery {
+    pub region_name: String,
+    pub storage: u16,
+    pub lodging: u16,
+    pub limited: bool,
+    pub max_storage: u16,
+    pub max_lodging: u16,
+    pub boundary: Vec<(u16, u16)>,
+    pub nearest: Vec<(u16, u16, u16)>,
+}
+
+impl InfeasibleQuery {
+    const NEAREST_COUNT: usize = 3;
+
+    pub(crate) fn from_chains(
+        region_name: &str,
+        storage: u16,
+        lodging: u16,
+        chains: &[Chain],
+    ) -> Self {
+        let max_storage = chains.iter().map(|c| c.storage).max().unwrap_or(0);
+        let max_lodging = chains.iter().map(|c| c.lodging).max().unwrap_or(0);
+
+        let boundary: Vec<(u16, u16)> = (0..=max_lodging)
+            .filter_map(|l| {
+                chains
+                    .iter()
+                    .filter(|c| c.lodging >= l)
+                    .map(|c| c.storage)
+                    .max()
+                    .map(|s| (l, s))
+            })
+            .collect();
+
+        let mut error = Self {
+            region_name: region_name.to_owned(),
+            storage,
+            lodging,
+            limited: false,
+            max_storage,
+            max_lodging,
+            boundary,
+            nearest: vec![],
+        };
+        error.set_nearest(chains);
+        error
+    }
+
+    /// Sets the front points of `chains` falling least short of the request, cheapest first.
+    pub(crate) fn set_nearest(&mut self, chains: &[Chain]) {
+        let shortfall = |c: &Chain| {
+            self.storage.saturating_sub(c.storage) as usize
+                + self.lodging.saturating_sub(c.lodging) as usize
+        };
+        let mut nearest: Vec<_> = chains
+            .iter()
+            .map(|c| (shortfall(c), c.cost, c.storage, c.lodging))
+            .collect();
+        nearest.sort_unstable();
+        nearest.dedup_by_key(|(shortfall, _, _, _)| *shortfall);
+        self.nearest = nearest
+            .iter()
+            .take(Self::NEAREST_COUNT)
+            .map(|(_, cost, s, l)| (*s, *l, *cost))
+            .collect();
+    }
+
+    pub(crate) fn from_region(region: &RegionNodes, storage: u16, lodging: u16) -> Self {
+        Self {
+            region_name: region.region_name.clone(),
+            storage,
+            lodging,
+            limited: false,
+            max_storage: region.max_warehouse_count as u16,
+            max_lodging: region.max_worker_count as u16,
+            boundary: vec![],
+            nearest: vec![],
+        }
+    }
+}
+
+impl fmt::Display for InfeasibleQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "No {} chain{} provides {} storage and {} lodging.",
+            self.region_name,
+            if self.limited {
+                " within the maximums"
+            } else {
+                ""
+            },
+            self.storage,
+            self.lodging
+        )?;
+        write!(
+            f,
+            "\n The maximum storage is {} and the maximum lodging is {}.",
+            self.max_storage, self.max_lodging
+        )?;
+        if !self.boundary.is_empty() {
+            let points: Vec<_> = self
+                .boundary
+                .iter()
+                .map(|(l, s)| format!("L{l}:S{s}"))
+                .collect();
+            write!(f, "\n Most storage by lodging: {}", points.join(" "))?;
+        }
+        if !self.nearest.is_empty() {
+            write!(f, "\n Closest achievable:")?;
+            for (s, l, cost) in self.nearest.iter() {
+                write!(f, "\n   -S {s} -L {l} for {cost} CP")?;
+            }
+        }
+        std::result::Result::Ok(())
+    }
+}
+
+impl std::error::Error for InfeasibleQuery {}
+
+fn has_window(cli: &Cli) -> bool {
+    cli.max_cost.is_some() || cli.max_storage.is_some() || cli.max_lodging.is_some()
+}
+
+/// Retains the chains matching the -S/-L minimums and maximums.
+///
+/// Fails with an [`InfeasibleQuery`] when no chain matches.
+pub(crate) fn filter_by_storage_and_lodging(
+    chains: &mut ChainVec,
+    cli: &Cli,
+    region_name: &str,
+) -> Result<()> {
+    let has_minimums = cli.storage.is_some() || cli.lodging.is_some();
+    if !has_minimums && !has_window(cli) && !cli.all_dominant {
+        return Ok(());
     }
     let lodging = cli.lodging.unwrap_or(0);
     let storage = cli.storage.unwrap_or(0);
-    chains.retain(|chain| chain.storage >= storage && chain.lodging >= lodging);
+    let max_cost = cli.max_cost.unwrap_or(u16::MAX);
+    let max_lodging = cli.max_lodging.unwrap_or(u16::MAX);
+    let max_storage = cli.max_storage.unwrap_or(u16::MAX);
+    let all_chains = chains.clone();
+    chains.retain(|chain| {
+        (storage..=max_storage).contains(&chain.storage)
+            && (lodging..=max_lodging).contains(&chain.lodging)
+            && chain.cost <= max_cost
+    });
     if chains.is_empty() {
-        println!("No chains match your query! Check your storage and lodging values.");
-        process::exit(1);
+        let mut error = InfeasibleQuery::from_chains(region_name, storage, lodging, &all_chains);
+        error.limited = has_window(cli);
+        let windowed: ChainVec = all_chains
+            .into_iter()
+            .filter(|chain| {
+                chain.storage <= max_storage
+                    && chain.lodging <= max_lodging
+                    && chain.cost <= max_cost
+            })
+            .collect();
+        if !windowed.is_empty() {
+            error.set_nearest(&windowed);
+        }
+        return Err(error.into());
+    }
+    if cli.all_dominant || !has_minimums {
+        chains.sort_by_key(|chain| (chain.cost, chain.storage, chain.lodging));
+        return Ok(());
     }
     let cost_anchor = chains.iter().min_by_key(|chain| chain.cost);
     let cost_anchor = cost_anchor.unwrap().cost;
     chains.retain(|chain| chain.cost == cost_anchor);
+    Ok(())
 }
 
 fn initialize_region(cli: &Cli) -> Result<String> {
-    let region_name = cli.region.as_ref().unwrap().clone();
-    let region_name = region_name.replace('_', " ");
-    let region = get_region_buildings(Some(region_name.clone()))?;
-    let region = RegionNodes::new(region.get(&region_name).unwrap())?;
+    let region = get_region_buildings(cli.region.clone())?;
+    let (region_name, buildings) = region.first_key_value().unwrap();
+    let region = RegionNodes::new(buildings)?;
     let _region = REGION.get_or_init(|| region);
-    Ok(region_name)
+    Ok(region_name.to_owned())
+}
+
+fn live_chains(cli: &Cli, region_name: &str, selection: &BuildingSelection) -> Result<ChainVec> {
+    if cli.storage.is_none() && cli.lodging.is_none() {
+        bail!(
+            "The chains for {} are missing or don't match houseinfo.json.\n \
+            Use '-S'/'-L' to solve a single chain or '--optimize' to regenerate them.",
+            region_name
+        );
+    }
+    if has_window(cli) || cli.all_dominant {
+        bail!(
+            "Listing chains within maximums needs current chains for {}.\n \
+            Use '--optimize' to regenerate them.",
+            region_name
+        );
+    }
+    let storage = cli.storage.unwrap_or(0);
+    let lodging = cli.lodging.unwrap_or(0);
+
+    let mut cached = CachedChain::many_from_cache_json(region_name, selection);
+    if let Some(hit) = cached.iter().find(|c| c.answers(storage, lodging)) {
+        info!(
+            "using cached chain for S{} L{}",
+            hit.query_storage, hit.query_lodging
+        );
+        return Ok(vec![hit.chain.clone()]);
+    }
+
+    info!("solving S{storage} L{lodging} live...");
+    let region = REGION.get().unwrap();
+    let Some(chain) = optimize_point(region, storage as usize, lodging as usize, selection) else {
+        return Err(InfeasibleQuery::from_region(region, storage, lodging).into());
+    };
+    if cli.cache {
+        cached.push(CachedChain {
+            query_storage: storage,
+            query_lodging: lodging,
+            chain: chain.clone(),
+        });
+        CachedChain::write_many(region_name, selection, &cached)?;
+    }
+    Ok(vec![chain])
+}
+
+fn near_optimal_chains(cli: &Cli, selection: &BuildingSelection) -> Result<ChainVec> {
+    let storage = cli.storage.unwrap_or(0);
+    let lodging = cli.lodging.unwrap_or(0);
+    let region = REGION.get().unwrap();
+    info!(
+        "solving S{storage} L{lodging} within {} CP...",
+        cli.within.unwrap()
+    );
+    let chains = optimize_within(
+        region,
+        storage as usize,
+        lodging as usize,
+        cli.within.unwrap() as usize,
+        cli.limit,
+        selection,
+    );
+    if chains.is_empty() {
+        return Err(InfeasibleQuery::from_region(region, storage, lodging).into());
+    }
+    Ok(chains)
 }
 
 pub(crate) fn list_buildings(cli: Cli) -> Result<()> {
     let region_name = initialize_region(&cli)?;
-    let mut chains = Chain::many_from_region_json(&region_name)?;
-    filter_by_storage_and_lodging(&mut chains, cli);
+    let selection =
+        BuildingSelection::from_cli(&cli)?.for_region(&REGION.get().unwrap().buildings)?;
+    if cli.within.is_some() {
+        // The chain files only hold dominant chains so near-optimal ones are always solved.
+        let chains = near_optimal_chains(&cli, &selection)?;
+        let cheapest = chains[0].cost;
+        println!();
+        for (i, chain) in chains.iter().enumerate() {
+            let label = format!(
+                "{} of {}, +{} CP",
+                i + 1,
+                chains.len(),
+                chain.cost - cheapest
+            );
+            println!("{}", style(label).dim());
+            print_chain(&cli, chain);
+        }
+        return Ok(());
+    }
+    let mut front = None;
+    let chains = if let Some(mut chains) = current_chains(REGION.get().unwrap(), &selection) {
+        if cli.explain {
+            front = Some(chains.clone());
+        }
+        filter_by_storage_and_lodging(&mut chains, &cli, &region_name)?;
+        chains
+    } else {
+        live_chains(&cli, &region_name, &selection)?
+    };
 
-    if chains.is_empty() {
-        let region = REGION.get().unwrap();
-        error!(
-            "The maximum storage and lodging counts for {} are {} and {}.",
-            region.region_name, region.max_warehouse_count, region.max_worker_count
+    println!();
+    chains.iter().for_each(|chain| print_chain(&cli, chain));
+    if cli.explain {
+        print_explanation(
+            REGION.get().unwrap(),
+            &selection,
+            front.as_deref(),
+            &chains[0],
+            cli.storage.unwrap_or(0),
+            cli.lodging.unwrap_or(0),
         );
-        bail!(std::io::ErrorKind::InvalidInput);
-    } else {
-        println!();
-        chains.iter().for_each(|chain| println!("{chain}"));
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::buildings_fingerprint;
+
+    #[test]
+    fn tracked_chains_match_their_region() {
+        for buildings in get_region_buildings(None).unwrap().values() {
+            let region = RegionNodes::new(buildings).unwrap();
+            assert!(
+                current_chains(&region, &BuildingSelection::default()).is_some(),
+                "{}",
+                region.region_name
+            );
+        }
+    }
+
+    #[test]
+    fn any_building_change_changes_the_fingerprint() {
+        let region = get_region_buildings(Some("Velia".to_string())).unwrap();
+        let region = RegionNodes::new(region.values().next().unwrap()).unwrap();
+        let fingerprint = buildings_fingerprint(&region.buildings);
+        let changes: Vec<fn(&mut BuildingMap, usize)> = vec![
+            |buildings, key| buildings.get_mut(&key).unwrap().cost += 1,
+            |buildings, key| buildings.get_mut(&key).unwrap().warehouse_count += 1,
+            |buildings, key| buildings.get_mut(&key).unwrap().worker_count += 1,
+            |buildings, key| buildings.get_mut(&key).unwrap().need_key = key + 1,
+            |buildings, key| {
+                buildings.remove(&key);
+            },
+            |buildings, key| {
+                let mut added = buildings[&key].clone();
+                added.key = 9999;
+                buildings.insert(added.key, added);
+            },
+        ];
+        // Chains need not use a building for its changes to matter.
+        for (key, change) in region
+            .buildings
+            .keys()
+            .flat_map(|k| changes.iter().map(|c| (*k, c)))
+        {
+            let mut buildings = region.buildings.clone();
+            change(&mut buildings, key);
+            assert_ne!(buildings_fingerprint(&buildings), fingerprint, "{key}");
+        }
+    }
+}
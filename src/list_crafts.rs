@@ -7,11 +7,6 @@ use std::collections::BTreeMap;
 type CraftCounts = BTreeMap<String, u32>;
 type RegionCraftingCounts = BTreeMap<String, CraftCounts>;
 
-lazy_static! {
-    static ref CRAFT_USAGE: IndexedStringMap =
-        read_csv_data("HouseInfoReceipe.csv").expect("Error reading HouseInfoReceipe.csv");
-}
-
 fn print_listing(crafts_summary: RegionCraftingCounts) -> Result<()> {
     for (region, usages) in crafts_summary.iter() {
         let mut table = Table::new();
@@ -57,10 +52,7 @@ fn summarize_crafts(regions_buildings: RegionBuildingMap) -> Result<RegionCrafti
 }
 
 pub(crate) fn list_crafts(region: Option<String>) -> Result<()> {
-    let mut regions_buildings = parse_houseinfo_data()?;
-    if let Some(region) = region {
-        regions_buildings.retain(|k, _| *k == region);
-    }
+    let regions_buildings = get_region_buildings(region)?;
     let crafts_summary = summarize_crafts(regions_buildings)?;
     print_listing(crafts_summary)
 }
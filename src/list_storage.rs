@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
@@ -7,23 +7,27 @@ use anyhow::{Context, Ok, Result};
 use comfy_table::{Attribute, Cell, Table};
 use serde::Deserialize;
 
+use crate::cli_args::ContextType;
 use crate::houseinfo::*;
 
 type ChainVec = Vec<Chain>;
 
 #[derive(Debug, Deserialize, Eq, PartialEq)]
 struct Chain {
-    total_storage: u16,
+    total_storage: Option<u16>,
+    total_lodging: Option<u16>,
     total_cost: u16,
-    storage: u16,
     cost: u16,
     indices: Vec<usize>,
     states: Vec<usize>,
 }
 
 impl Chain {
-    fn all_from_regions_storage_json() -> Result<ChainVec> {
-        let filename = "all_regions_storage.json";
+    fn all_from_regions_json(context: &ContextType) -> Result<ChainVec> {
+        let filename = match context {
+            ContextType::S | ContextType::Storage => "all_regions_storage.json",
+            ContextType::L | ContextType::Lodging => "all_regions_lodging.json",
+        };
         let path = PathBuf::from("./data/housecraft").join(filename);
         let file = File::open(&path).with_context(|| format!("Can't find {}", path.display()))?;
         let reader = BufReader::new(file);
@@ -45,12 +49,91 @@ fn region_name_from_chain(chain: &Chain, regions_buildings: &RegionBuildingMap)
     Err(anyhow::anyhow!("Region not found for id {}", region_id))
 }
 
-/// Processes and emits storage chain entries in table format from `all_regions_storage.json`
-pub(crate) fn list_storage() -> Result<()> {
-    let regions_buildings = parse_houseinfo_data()?;
-    let chains = Chain::all_from_regions_storage_json()?;
+/// The slots `building` gives in `state` for the context.
+fn state_value(context: &ContextType, building: &Building, state: usize) -> i64 {
+    match (context, state) {
+        (ContextType::S | ContextType::Storage, 1) => building.warehouse_count as i64,
+        (ContextType::L | ContextType::Lodging, 2) => building.worker_count as i64,
+        _ => 0,
+    }
+}
+
+/// A building's change from the region's previously listed chain to the next one.
+#[derive(Debug)]
+struct Step {
+    key: usize,
+    note: &'static str,
+    cost: i64,
+    value: i64,
+}
 
-    let mut seen_buildings: HashMap<usize, HashSet<usize>> = HashMap::new();
+/// Returns the rentals, state switches and releases taking a region from its `vested`
+/// buildings (key to state) to `chain`'s.
+fn vested_steps(
+    context: &ContextType,
+    buildings: &BuildingMap,
+    vested: &HashMap<usize, usize>,
+    chain: &Chain,
+) -> Vec<Step> {
+    let next: HashMap<usize, usize> = chain
+        .indices
+        .iter()
+        .copied()
+        .zip(chain.states.iter().copied())
+        .skip(1)
+        .collect();
+    let mut steps = vec![];
+    for (key, state) in chain.indices.iter().zip(chain.states.iter()).skip(1) {
+        let building = &buildings[key];
+        let value = state_value(context, building, *state);
+        match vested.get(key) {
+            None => steps.push(Step {
+                key: *key,
+                note: "",
+                cost: building.cost as i64,
+                value,
+            }),
+            Some(previous) if previous != state => steps.push(Step {
+                key: *key,
+                note: " (switched)",
+                cost: 0,
+                value: value - state_value(context, building, *previous),
+            }),
+            Some(_) => {}
+        }
+    }
+    let mut released: Vec<(&usize, &usize)> = vested
+        .iter()
+        .filter(|(key, _)| !next.contains_key(key))
+        .collect();
+    released.sort();
+    for (key, state) in released {
+        let building = &buildings[key];
+        steps.push(Step {
+            key: *key,
+            note: " (released)",
+            cost: -(building.cost as i64),
+            value: -state_value(context, building, *state),
+        });
+    }
+    steps
+}
+
+/// Processes and emits storage (or lodging) chain entries in table format from
+/// `all_regions_storage.json` (or `all_regions_lodging.json`)
+pub(crate) fn list_storage(context: ContextType) -> Result<()> {
+    let regions_buildings = parse_houseinfo_data()?;
+    let chains = Chain::all_from_regions_json(&context)?;
+    let label = match context {
+        ContextType::S | ContextType::Storage => "S",
+        ContextType::L | ContextType::Lodging => "L",
+    };
+
+    let buildings: BuildingMap = regions_buildings
+        .values()
+        .flat_map(|map| map.iter().map(|(k, b)| (*k, b.clone())))
+        .collect();
+    let mut vested_buildings: HashMap<usize, HashMap<usize, usize>> = HashMap::new();
 
     let mut table = Table::new();
     table.load_preset(HOUSECRAFT_TABLE_STYLE);
@@ -58,16 +141,16 @@ pub(crate) fn list_storage() -> Result<()> {
         Cell::new("Region").add_attribute(Attribute::Dim),
         Cell::new("Building").add_attribute(Attribute::Dim),
         Cell::new("C").add_attribute(Attribute::Dim),
-        Cell::new("S").add_attribute(Attribute::Dim),
-        Cell::new("S/C").add_attribute(Attribute::Dim),
+        Cell::new(label).add_attribute(Attribute::Dim),
+        Cell::new(format!("{label}/C")).add_attribute(Attribute::Dim),
         Cell::new("ttlC").add_attribute(Attribute::Dim),
-        Cell::new("ttlS").add_attribute(Attribute::Dim),
-        Cell::new("ttlS/ttlC").add_attribute(Attribute::Dim),
+        Cell::new(format!("ttl{label}")).add_attribute(Attribute::Dim),
+        Cell::new(format!("ttl{label}/ttlC")).add_attribute(Attribute::Dim),
     ]);
 
     let mut previous_region_name = "".to_string();
-    let mut total_cost = 0;
-    let mut total_storage = 0;
+    let mut total_cost: i64 = 0;
+    let mut total_value: i64 = 0;
 
     for (i, chain) in chains.iter().enumerate() {
         let region_id = chain.indices[0];
@@ -77,20 +160,10 @@ pub(crate) fn list_storage() -> Result<()> {
         }
 
         let region_name = region_name_from_chain(chain, &regions_buildings)?;
-        let seen = seen_buildings.entry(region_id).or_default();
-
-        for (index, _state) in chain.indices.iter().zip(chain.states.iter()).skip(1) {
-            if seen.contains(index) {
-                continue;
-            }
-            seen.insert(*index);
-
-            let building = regions_buildings
-                .values()
-                .flat_map(|map| map.values())
-                .find(|b| b.key == *index)
-                .unwrap();
+        let vested = vested_buildings.entry(region_id).or_default();
 
+        for step in vested_steps(&context, &buildings, vested, chain) {
+            let building = &buildings[&step.key];
             let region_name_value = if region_name != previous_region_name {
                 previous_region_name = region_name.clone();
                 region_name.clone()
@@ -98,22 +171,38 @@ pub(crate) fn list_storage() -> Result<()> {
                 "".to_string()
             };
 
-            total_cost += building.cost;
-            total_storage += building.warehouse_count;
-            let sp_ratio = building.warehouse_count as f32 / building.cost as f32;
-            let ttlsp_ratio = total_storage as f32 / total_cost as f32;
+            total_cost += step.cost;
+            total_value += step.value;
+            let sp_ratio = if step.cost <= 0 {
+                "".to_string()
+            } else {
+                format!("{:.2}", step.value as f32 / step.cost as f32)
+            };
+            let ttlsp_ratio = total_value as f32 / total_cost as f32;
 
             table.add_row(vec![
                 &region_name_value,
-                &building.building_name,
-                &building.cost.to_string(),
-                &building.warehouse_count.to_string(),
-                &format!("{:.2}", sp_ratio),
+                &format!("{}{}", building.building_name, step.note),
+                &step.cost.to_string(),
+                &step.value.to_string(),
+                &sp_ratio,
                 &total_cost.to_string(),
-                &total_storage.to_string(),
+                &total_value.to_string(),
                 &format!("{:.2}", ttlsp_ratio),
             ]);
         }
+
+        *vested = chain
+            .indices
+            .iter()
+            .copied()
+            .zip(chain.states.iter().copied())
+            .skip(1)
+            .collect();
+        debug_assert_eq!(total_cost, chain.total_cost as i64);
+        if let Some(chain_total) = chain.total_storage.or(chain.total_lodging) {
+            debug_assert_eq!(total_value, chain_total as i64);
+        }
     }
 
     println!("\n{table}");
@@ -9,23 +9,42 @@ extern crate log;
 
 extern crate num_cpus;
 
+mod allocate;
+mod analyze;
 mod cli_args;
+mod estimate;
+mod explain;
 mod find_crafts;
 mod generate;
+mod highs_model;
 mod houseinfo;
 mod list_buildings;
 mod list_crafts;
 mod list_regions;
 mod list_storage;
+mod name_resolution;
 mod node_manipulation;
 mod optimize;
+mod owned;
+mod plan_crafts;
+mod plot;
+mod profile;
 mod region_nodes;
+mod render_tree;
+mod scheduler;
+mod selection;
+mod show_building;
+mod storage_index;
+mod transition;
+mod upgrade_path;
 
 use anyhow::{Ok, Result};
 use clap::{CommandFactory, Parser};
 use log::Level::Debug;
 
-use cli_args::Cli;
+use allocate::allocate;
+use analyze::analyze;
+use cli_args::{Cli, ContextType};
 use find_crafts::find_craft_buildings;
 use generate::generate;
 use list_buildings::list_buildings;
@@ -33,6 +52,15 @@ use list_crafts::list_crafts;
 use list_regions::list_regions;
 use list_storage::list_storage;
 use optimize::optimize;
+use owned::upgrade_owned;
+use plan_crafts::plan_crafts;
+use plot::plot;
+use profile::show_profile;
+use render_tree::render_tree;
+use show_building::show_building;
+use storage_index::build_storage_index;
+use transition::transition;
+use upgrade_path::upgrade_path;
 
 fn main() -> Result<()> {
     let mut cli = Cli::parse();
@@ -51,14 +79,38 @@ fn main() -> Result<()> {
         list_regions()?
     } else if cli.list_crafts {
         list_crafts(cli.region)?
+    } else if cli.list_lodging {
+        list_storage(ContextType::Lodging)?
     } else if cli.list_storage {
-        list_storage()?
+        list_storage(cli.context.unwrap_or(ContextType::Storage))?
     } else if let Some(craft) = cli.find_craft {
         find_craft_buildings(cli.region, craft)?
+    } else if cli.allocate {
+        allocate(&cli)?
+    } else if let Some(usages) = cli.plan_crafts {
+        plan_crafts(cli.region, usages)?
+    } else if cli.owned.is_some() {
+        upgrade_owned(&cli)?
+    } else if let Some(path) = cli.profile {
+        show_profile(&path, cli.region)?
+    } else if cli.transition {
+        transition(&cli)?
+    } else if cli.upgrade_path {
+        upgrade_path(&cli)?
+    } else if let Some(building) = cli.show_building {
+        show_building(cli.region, building)?
     } else if cli.generate {
         generate(&mut cli)?
     } else if cli.optimize {
         optimize(&mut cli)?
+    } else if cli.build_storage_index {
+        build_storage_index(&cli)?
+    } else if cli.tree {
+        render_tree(&cli)?
+    } else if cli.plot {
+        plot(&cli)?
+    } else if cli.analyze {
+        analyze(&cli)?
     } else if cli.region.is_some() {
         list_buildings(cli)?
     } else {
@@ -0,0 +1,304 @@
+//! Resolve user supplied region and building names.
+//!
+//! Queries may be given as a numeric key, as a name in any case with or without diacritics and
+//! with underscores in place of spaces, or as a unique prefix of a name. When a query matches
+//! more than one candidate the candidates are listed, and when it matches nothing the closest
+//! names are suggested.
+
+use anyhow::{bail, Result};
+
+use crate::houseinfo::*;
+
+const MAX_SUGGESTIONS: usize = 5;
+
+struct Candidate {
+    key: usize,
+    name: String,
+    normalized: String,
+}
+
+impl Candidate {
+    fn new(key: usize, name: &str) -> Self {
+        Self {
+            key,
+            name: name.to_string(),
+            normalized: normalize_name(name),
+        }
+    }
+}
+
+/// Returns `name` lowercased with diacritics folded, apostrophes dropped and underscores and
+/// runs of whitespace collapsed to single spaces.
+pub(crate) fn normalize_name(name: &str) -> String {
+    let folded: String = name
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '’'))
+        .map(|c| match c {
+            '_' => ' ',
+            _ => fold_diacritic(c),
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn fold_diacritic(c: char) -> char {
+    match c {
+        'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' | 'ā' => 'a',
+        'Á' | 'À' | 'Â' | 'Ä' | 'Ã' | 'Å' | 'Ā' => 'A',
+        'é' | 'è' | 'ê' | 'ë' | 'ē' => 'e',
+        'É' | 'È' | 'Ê' | 'Ë' | 'Ē' => 'E',
+        'í' | 'ì' | 'î' | 'ï' | 'ī' => 'i',
+        'Í' | 'Ì' | 'Î' | 'Ï' | 'Ī' => 'I',
+        'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' | 'ō' => 'o',
+        'Ó' | 'Ò' | 'Ô' | 'Ö' | 'Õ' | 'Ø' | 'Ō' => 'O',
+        'ú' | 'ù' | 'û' | 'ü' | 'ū' => 'u',
+        'Ú' | 'Ù' | 'Û' | 'Ü' | 'Ū' => 'U',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        'ç' => 'c',
+        'Ç' => 'C',
+        'ý' | 'ÿ' => 'y',
+        'Ý' => 'Y',
+        _ => c,
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn suggestions(query: &str, candidates: &[Candidate]) -> Vec<String> {
+    let max_distance = std::cmp::max(2, query.chars().count() / 3);
+    let mut scored: Vec<(usize, &Candidate)> = candidates
+        .iter()
+        .map(|c| {
+            // Compare against the same length prefix as well so that partial names still rank.
+            let prefix: String = c.normalized.chars().take(query.chars().count()).collect();
+            let distance = std::cmp::min(
+                edit_distance(query, &c.normalized),
+                edit_distance(query, &prefix) + 1,
+            );
+            (distance, c)
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    scored.sort_by(|a, b| a.0.cmp(&b.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    scored
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.name.clone())
+        .collect()
+}
+
+fn resolve(kind: &str, hint: &str, query: &str, candidates: &[Candidate]) -> Result<usize> {
+    if let Ok(key) = query.trim().parse::<usize>() {
+        let matched: Vec<_> = candidates.iter().filter(|c| c.key == key).collect();
+        if matched.len() == 1 {
+            return Ok(matched[0].key);
+        }
+    }
+
+    let normalized = normalize_name(query);
+    let exact: Vec<_> = candidates
+        .iter()
+        .filter(|c| c.normalized == normalized)
+        .collect();
+    if exact.len() == 1 {
+        return Ok(exact[0].key);
+    }
+
+    let matched: Vec<_> = if exact.is_empty() {
+        candidates
+            .iter()
+            .filter(|c| !normalized.is_empty() && c.normalized.starts_with(&normalized))
+            .collect()
+    } else {
+        exact
+    };
+
+    match matched.len() {
+        1 => Ok(matched[0].key),
+        0 => {
+            let suggested = if normalized.is_empty() {
+                vec![]
+            } else {
+                suggestions(&normalized, candidates)
+            };
+            if suggested.is_empty() {
+                bail!("Unable to find {kind} '{query}'.\n Use '{hint}'.");
+            }
+            bail!(
+                "Unable to find {kind} '{query}'. Did you mean:\n  {}",
+                suggested.join("\n  ")
+            );
+        }
+        _ => {
+            let mut names: Vec<_> = matched
+                .iter()
+                .map(|c| format!("{} ({})", c.name, c.key))
+                .collect();
+            names.sort();
+            bail!(
+                "The {kind} '{query}' is ambiguous. It matches:\n  {}",
+                names.join("\n  ")
+            );
+        }
+    }
+}
+
+/// Returns the region name matching `query` by region key, name or unique name prefix.
+pub(crate) fn resolve_region_name(
+    regions_buildings: &RegionBuildingMap,
+    query: &str,
+) -> Result<String> {
+    let candidates: Vec<_> = regions_buildings
+        .iter()
+        .filter_map(|(name, buildings)| {
+            buildings
+                .values()
+                .next()
+                .map(|b| Candidate::new(b.region_key, name))
+        })
+        .collect();
+    let key = resolve("region", "--list-regions", query, &candidates)?;
+    let name = candidates.iter().find(|c| c.key == key).unwrap();
+    Ok(name.name.clone())
+}
+
+/// Returns the building key matching `query` by character key, name or unique name prefix.
+pub(crate) fn resolve_building_key<'a>(
+    buildings: impl IntoIterator<Item = &'a Building>,
+    query: &str,
+) -> Result<usize> {
+    let candidates: Vec<_> = buildings
+        .into_iter()
+        .map(|b| Candidate::new(b.key, &b.building_name))
+        .collect();
+    resolve("building", "--find-craft", query, &candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidates() -> Vec<Candidate> {
+        vec![
+            Candidate::new(1, "Velia"),
+            Candidate::new(5, "Heidel"),
+            Candidate::new(32, "Calpheon City"),
+            Candidate::new(52, "Calpheon"),
+            Candidate::new(77, "Glish"),
+            Candidate::new(202, "Old Wisdom Tree"),
+            Candidate::new(221, "Grána"),
+            Candidate::new(229, "Valencia City"),
+            Candidate::new(601, "Nopsae's Byeot County"),
+        ]
+    }
+
+    #[test]
+    fn normalize_name_folds_case_diacritics_and_separators() {
+        assert_eq!(normalize_name("Grána"), "grana");
+        assert_eq!(normalize_name("ÁÉÍÓÚ Ññ Çç"), "aeiou nn cc");
+        assert_eq!(normalize_name("Calpheon_City"), "calpheon city");
+        assert_eq!(normalize_name("  Old   Wisdom\tTree "), "old wisdom tree");
+        assert_eq!(normalize_name("Nopsae's Byeot"), "nopsaes byeot");
+        assert_eq!(normalize_name("Nopsae’s Byeot"), "nopsaes byeot");
+        assert_eq!(normalize_name(""), "");
+        assert_eq!(normalize_name(" _ "), "");
+    }
+
+    #[test]
+    fn edit_distance_counts_insertions_deletions_and_substitutions() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("velia", "velia"), 0);
+        assert_eq!(edit_distance("vleia", "velia"), 2);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("grana", "grána"), 1);
+    }
+
+    #[test]
+    fn resolve_by_key_name_and_prefix() {
+        let candidates = candidates();
+        let resolve = |query| resolve("region", "--list-regions", query, &candidates);
+        assert_eq!(resolve("5").unwrap(), 5);
+        assert_eq!(resolve(" 229 ").unwrap(), 229);
+        assert_eq!(resolve("VELIA").unwrap(), 1);
+        assert_eq!(resolve("grana").unwrap(), 221);
+        assert_eq!(resolve("valencia_city").unwrap(), 229);
+        assert_eq!(resolve("nopsaes").unwrap(), 601);
+        assert_eq!(resolve("Old W").unwrap(), 202);
+    }
+
+    #[test]
+    fn resolve_prefers_an_exact_name_over_longer_prefix_matches() {
+        let candidates = candidates();
+        assert_eq!(
+            resolve("region", "--list-regions", "calpheon", &candidates).unwrap(),
+            52
+        );
+    }
+
+    #[test]
+    fn resolve_lists_ambiguous_prefixes_with_their_keys() {
+        let candidates = candidates();
+        let error = resolve("region", "--list-regions", "Calp", &candidates)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("ambiguous"));
+        assert!(error.contains("Calpheon (52)"));
+        assert!(error.contains("Calpheon City (32)"));
+    }
+
+    #[test]
+    fn resolve_suggests_close_names_or_the_hint() {
+        let candidates = candidates();
+        let error = resolve("region", "--list-regions", "Heidle", &candidates)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Did you mean"));
+        assert!(error.contains("Heidel"));
+
+        let error = resolve("region", "--list-regions", "", &candidates)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("--list-regions"));
+
+        let error = resolve("region", "--list-regions", "zzzzzzzzzzzz", &candidates)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("Use '--list-regions'"));
+    }
+
+    #[test]
+    fn resolve_falls_back_to_names_for_unknown_numeric_keys() {
+        let candidates = vec![Candidate::new(1, "Velia"), Candidate::new(2, "2 Velia")];
+        assert_eq!(
+            resolve("building", "--find-craft", "2", &candidates).unwrap(),
+            2
+        );
+        assert!(resolve("building", "--find-craft", "3", &candidates).is_err());
+    }
+
+    #[test]
+    fn suggestions_are_ordered_by_distance_then_name() {
+        let candidates = vec![
+            Candidate::new(1, "Velib"),
+            Candidate::new(2, "Velia"),
+            Candidate::new(3, "Heidel"),
+        ];
+        assert_eq!(suggestions("velix", &candidates), vec!["Velia", "Velib"]);
+    }
+}
@@ -1,9 +1,8 @@
 use std::collections::HashMap;
-use std::ffi::{c_void, CString};
+use std::ffi::c_void;
 use std::fs::{self, File};
 use std::io::Write;
 use std::path::PathBuf;
-use std::ptr::null;
 
 use anyhow::{Ok, Result};
 use highs_sys::*;
@@ -13,10 +12,15 @@ use regex::Regex;
 use serde::Serialize;
 use serde_json::to_string_pretty;
 
-use crate::cli_args::Cli;
+use crate::cli_args::{Cli, TieBreak};
+use crate::highs_model::HighsModel;
 use crate::houseinfo::*;
+use crate::list_buildings::{self, Alternative};
 use crate::node_manipulation::{count_subtrees, count_subtrees_multistate};
 use crate::region_nodes::RegionNodes;
+use crate::scheduler::{check_region_results, for_each_region};
+use crate::selection::{chains_path, write_fingerprint, BuildingSelection};
+use crate::storage_index::build_storage_index;
 
 type ChainVec = Vec<Chain>;
 
@@ -43,14 +47,17 @@ struct Chain {
     cost: usize,
     indices: Vec<usize>,
     states: Vec<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<Alternative>,
 }
 
 impl Chain {
     fn from_highs(highs: &SubsetModel, region: &RegionNodes) -> Self {
-        let cost = unsafe { Highs_getObjectiveValue(highs.highs_ptr).round() as usize };
-        let col_values = unsafe { highs.solution_col_values() };
+        // The objective may include a tiebreak term so the cost is summed from the solution.
+        let col_values = highs.solution_col_values();
 
         let num_nodes = region.num_nodes;
+        let mut cost = 0;
         let mut warehouse_count = 0;
         let mut worker_count = 0;
         let mut indices = vec![0];
@@ -58,6 +65,7 @@ impl Chain {
 
         for (i, col) in col_values.chunks_exact(3).take(num_nodes).enumerate() {
             if col[0] == 1 {
+                cost += region.costs[i];
                 if col[1] == 1 {
                     // state_1_flag
                     warehouse_count += region.warehouse_counts[i];
@@ -78,6 +86,7 @@ impl Chain {
             cost,
             indices,
             states,
+            alternatives: vec![],
         };
         trace!("{:?}", chain);
         chain
@@ -95,23 +104,36 @@ impl Chain {
     }
 }
 
-struct SubsetModel {
-    highs_ptr: *mut c_void,
+pub(crate) struct SubsetModel {
+    highs: HighsModel,
+    pub item_flags: HashMap<u32, i32>,
+    pub state_1_flags: HashMap<u32, i32>,
+    pub state_2_flags: HashMap<u32, i32>,
+    pub selection_rows: HashMap<u32, i32>,
+    /// State flags whose state adds storage or lodging.
+    value_flags: Vec<i32>,
+    root_flag: i32,
+    state_sum_rows: (HighsInt, HighsInt),
 }
 
 impl SubsetModel {
     pub fn new(region: &RegionNodes, worker_id: usize) -> Self {
         let mut model = SubsetModel {
-            highs_ptr: unsafe { Highs_create() },
+            highs: HighsModel::new(Some(&format!("subset_select_{worker_id}"))),
+            item_flags: HashMap::new(),
+            state_1_flags: HashMap::new(),
+            state_2_flags: HashMap::new(),
+            selection_rows: HashMap::new(),
+            value_flags: vec![],
+            root_flag: 0,
+            state_sum_rows: (0, 0),
         };
-        unsafe {
-            Self::set_options(&mut model, worker_id);
-            Self::initialize(&mut model, region);
-        }
+        model.highs.set_int_option("threads", 1);
+        model.initialize(region);
         model
     }
 
-    unsafe fn initialize(&mut self, region: &RegionNodes) {
+    fn initialize(&mut self, region: &RegionNodes) {
         let items: Vec<_> = region.children.iter().map(|x| *x as u32).collect();
         let item_reqs: Vec<_> = region.parents.iter().map(|x| *x as u32).collect();
         let state_1_values: Vec<_> = region.warehouse_counts.iter().map(|x| *x as f64).collect();
@@ -120,6 +142,7 @@ impl SubsetModel {
         let mut item_flags: HashMap<u32, i32> = HashMap::new();
         let mut state_1_flags: HashMap<u32, i32> = HashMap::new();
         let mut state_2_flags: HashMap<u32, i32> = HashMap::new();
+        let mut selection_rows: HashMap<u32, i32> = HashMap::new();
 
         self.initialize_flag_variables(
             &items,
@@ -134,12 +157,33 @@ impl SubsetModel {
             &item_flags,
             &state_1_flags,
             &state_2_flags,
+            &mut selection_rows,
         );
         self.initialize_state_value_sum_constraints(&items, &state_1_flags, &state_1_values);
         self.initialize_state_value_sum_constraints(&items, &state_2_flags, &state_2_values);
+        let num_rows = self.highs.num_rows();
+        self.state_sum_rows = (num_rows - 2, num_rows - 1);
+
+        self.value_flags = items
+            .iter()
+            .enumerate()
+            .flat_map(|(i, item)| {
+                [
+                    (state_1_flags[item], state_1_values[i]),
+                    (state_2_flags[item], state_2_values[i]),
+                ]
+            })
+            .filter(|(_, value)| *value > 0.0)
+            .map(|(flag, _)| flag)
+            .collect();
+        self.root_flag = item_flags[&items[0]];
+        self.item_flags = item_flags;
+        self.state_1_flags = state_1_flags;
+        self.state_2_flags = state_2_flags;
+        self.selection_rows = selection_rows;
     }
 
-    unsafe fn initialize_flag_variables(
+    fn initialize_flag_variables(
         &mut self,
         items: &[u32],
         costs: &[f64],
@@ -148,28 +192,13 @@ impl SubsetModel {
         state_2_flags: &mut HashMap<u32, i32>,
     ) {
         // Map {item: column_id} since HiGHS doesn't have assignment/retrieval by name yet.
-
-        let highs = self.highs_ptr;
-        let mut column_id = 0;
         for (i, item) in items.iter().enumerate() {
             // item_flags (with objective item selection costs)
-            if Highs_addCol(highs, costs[i], 0.0, 1.0, 0, null(), null()) == kHighsStatusOk {
-                Highs_changeColIntegrality(highs, column_id, kHighsVarTypeInteger);
-                item_flags.insert(*item, column_id);
-                column_id += 1;
-            }
+            item_flags.insert(*item, self.highs.add_binary_col(costs[i]));
             // state_1_flags
-            if Highs_addCol(highs, 0.0, 0.0, 1.0, 0, null(), null()) == kHighsStatusOk {
-                Highs_changeColIntegrality(highs, column_id, kHighsVarTypeInteger);
-                state_1_flags.insert(*item, column_id);
-                column_id += 1;
-            }
+            state_1_flags.insert(*item, self.highs.add_binary_col(0.0));
             // state_2_flags
-            if Highs_addCol(highs, 0.0, 0.0, 1.0, 0, null(), null()) == kHighsStatusOk {
-                Highs_changeColIntegrality(highs, column_id, kHighsVarTypeInteger);
-                state_2_flags.insert(*item, column_id);
-                column_id += 1;
-            }
+            state_2_flags.insert(*item, self.highs.add_binary_col(0.0));
         }
     }
 
@@ -186,7 +215,7 @@ impl SubsetModel {
         item_req_tree
     }
 
-    unsafe fn initialize_item_requirement_constraints(
+    fn initialize_item_requirement_constraints(
         &mut self,
         items: &[u32],
         item_flags: &HashMap<u32, i32>,
@@ -195,7 +224,7 @@ impl SubsetModel {
         // The item parent <- child requirements constraints.
         // Transitive; ensures children must have all ancestors back to root.
 
-        let highs_inf = Highs_getInfinity(self.highs_ptr);
+        let highs_inf = self.highs.infinity();
         let item_req_tree = Self::initialize_item_req_tree(items, item_reqs);
 
         for (parent, children) in item_req_tree.iter() {
@@ -204,67 +233,44 @@ impl SubsetModel {
                     continue;
                 }
                 // item_flags[child] - item_flags[parent] <= 0
-                let aindex: [i32; 2] = [item_flags[child], item_flags[parent]];
-                let avalue: [f64; 2] = [1.0, -1.0];
-                Highs_addRow(
-                    self.highs_ptr,
+                self.highs.add_row(
                     -highs_inf,
                     0.0,
-                    aindex.len() as i32,
-                    aindex.as_ptr(),
-                    avalue.as_ptr(),
+                    &[item_flags[child], item_flags[parent]],
+                    &[1.0, -1.0],
                 );
             }
         }
     }
 
-    unsafe fn initialize_item_selection_constraints(
+    fn initialize_item_selection_constraints(
         &mut self,
         items: &[u32],
         item_flags: &HashMap<u32, i32>,
         state_1_flags: &HashMap<u32, i32>,
         state_2_flags: &HashMap<u32, i32>,
+        selection_rows: &mut HashMap<u32, i32>,
     ) {
         // Item selection constraint: one state on flagged items, no state otherwise.
         for item in items.iter() {
             if *item == items[0] {
                 // Root item: enforce state_1_flags[0] = 0 and state_2_flags[0] = 0
-                let aindex: [i32; 1] = [state_1_flags[item]];
-                let avalue: [f64; 1] = [1.0];
-                Highs_addRow(
-                    self.highs_ptr,
-                    0.0,
-                    0.0,
-                    1,
-                    aindex.as_ptr(),
-                    avalue.as_ptr(),
-                );
-                let aindex: [i32; 1] = [state_2_flags[item]];
-                Highs_addRow(
-                    self.highs_ptr,
-                    0.0,
-                    0.0,
-                    1,
-                    aindex.as_ptr(),
-                    avalue.as_ptr(),
-                );
+                self.highs.add_row(0.0, 0.0, &[state_1_flags[item]], &[1.0]);
+                self.highs.add_row(0.0, 0.0, &[state_2_flags[item]], &[1.0]);
                 continue;
             }
             // state_1_flags[child] + state_2_flags[child] - items_flag[child] == 0
-            let aindex: [i32; 3] = [state_1_flags[item], state_2_flags[item], item_flags[item]];
-            let avalue: [f64; 3] = [1.0, 1.0, -1.0];
-            Highs_addRow(
-                self.highs_ptr,
+            selection_rows.insert(*item, self.highs.num_rows());
+            self.highs.add_row(
                 0.0,
                 0.0,
-                aindex.len() as i32,
-                aindex.as_ptr(),
-                avalue.as_ptr(),
+                &[state_1_flags[item], state_2_flags[item], item_flags[item]],
+                &[1.0, 1.0, -1.0],
             );
         }
     }
 
-    unsafe fn initialize_state_value_sum_constraints(
+    fn initialize_state_value_sum_constraints(
         &mut self,
         items: &[u32],
         state_flags: &HashMap<u32, i32>,
@@ -280,67 +286,118 @@ impl SubsetModel {
             }
         }
         // lb is set to an arbitrary value for now and is assigned prior to solving the model.
-        Highs_addRow(
-            self.highs_ptr,
-            9999.0,
-            Highs_getInfinity(self.highs_ptr),
-            aindex.len() as i32,
-            aindex.as_ptr(),
-            avalue.as_ptr(),
-        );
+        let highs_inf = self.highs.infinity();
+        self.highs.add_row(9999.0, highs_inf, &aindex, &avalue);
     }
 
-    fn mut_ptr(&mut self) -> *mut c_void {
-        self.highs_ptr
+    /// Fixes the excluded items as never selected and the included items as always selected.
+    pub fn fix_selection(&mut self, selection: &BuildingSelection) {
+        for (keys, value) in [(&selection.excluded, 0.0), (&selection.included, 1.0)] {
+            for key in keys.iter() {
+                if let Some(item_flag) = self.item_flags.get(&(*key as u32)) {
+                    let highs = self.highs.mut_ptr();
+                    unsafe { Highs_changeColBounds(highs, *item_flag, value, value) };
+                }
+            }
+        }
     }
 
-    unsafe fn set_options(&mut self, worker_id: usize) {
-        let do_logging = log_enabled!(log::Level::Trace) as i32;
+    /// Adds `tiebreak` to the objective as a secondary term worth less than 1 CP in total.
+    pub fn set_tiebreak(&mut self, region: &RegionNodes, tiebreak: TieBreak) {
+        let highs = self.mut_ptr();
+        let epsilon = match tiebreak {
+            TieBreak::Storage => 1.0 / (region.max_warehouse_count + 1) as f64,
+            _ => 1.0 / region.num_nodes as f64,
+        };
+        for (i, item) in region.children.iter().enumerate().skip(1) {
+            let item = *item as u32;
+            unsafe {
+                match tiebreak {
+                    TieBreak::Buildings => {
+                        let cost = region.costs[i] as f64 + epsilon;
+                        Highs_changeColCost(highs, self.item_flags[&item], cost);
+                    }
+                    TieBreak::PassThrough => {
+                        if region.warehouse_counts[i] == 0 {
+                            Highs_changeColCost(highs, self.state_1_flags[&item], epsilon);
+                        }
+                        if region.worker_counts[i] == 0 {
+                            Highs_changeColCost(highs, self.state_2_flags[&item], epsilon);
+                        }
+                    }
+                    TieBreak::Storage => {
+                        let reward = -(region.warehouse_counts[i] as f64) * epsilon;
+                        Highs_changeColCost(highs, self.state_1_flags[&item], reward);
+                    }
+                }
+            }
+        }
+        self.require_exact_optimum();
+    }
 
-        let option = CString::new("output_flag").unwrap();
-        Highs_setBoolOptionValue(self.highs_ptr, option.as_ptr(), do_logging);
+    /// Solves to a zero relative gap so objective terms worth less than 1 CP are honoured.
+    ///
+    /// The default relative gap would allow solutions off by more than such a term.
+    pub fn require_exact_optimum(&mut self) {
+        self.highs.set_double_option("mip_rel_gap", 0.0);
+    }
 
-        let option = CString::new("log_to_console").unwrap();
-        Highs_setBoolOptionValue(self.highs_ptr, option.as_ptr(), do_logging);
+    pub fn mut_ptr(&mut self) -> *mut c_void {
+        self.highs.mut_ptr()
+    }
 
-        if do_logging == 1 {
-            let option = CString::new("log_file").unwrap();
-            let option_value =
-                CString::new(format!("subset_select_{worker_id}_highs.log")).unwrap();
-            Highs_setStringOptionValue(self.highs_ptr, option.as_ptr(), option_value.as_ptr());
-        }
+    /// Returns the (storage, lodging) sum rows whose lower bounds are the usage targets.
+    pub fn state_sum_rows(&self) -> (HighsInt, HighsInt) {
+        self.state_sum_rows
+    }
 
-        let option = CString::new("threads").unwrap();
-        Highs_setIntOptionValue(self.highs_ptr, option.as_ptr(), 1);
+    /// Adds a row excluding the state assignment of `col_values` from later solutions.
+    ///
+    /// Only the states adding storage or lodging are compared so a zero slot building rented in
+    /// the other state isn't taken for a different assignment.
+    pub fn add_no_good_cut(&mut self, col_values: &[u32]) {
+        // sum(value_flags set in col_values) - sum(value_flags unset) <= count(set) - 1
+        let mut aindex: Vec<i32> = Vec::new();
+        let mut avalue: Vec<f64> = Vec::new();
+        let mut set_count = 0.0;
+        for flag in self.value_flags.iter() {
+            aindex.push(*flag);
+            if col_values[*flag as usize] == 1 {
+                avalue.push(1.0);
+                set_count += 1.0;
+            } else {
+                avalue.push(-1.0);
+            }
+        }
+        let highs_inf = self.highs.infinity();
+        self.highs
+            .add_row(-highs_inf, set_count - 1.0, &aindex, &avalue);
     }
 
-    unsafe fn solution_col_values(&self) -> Vec<u32> {
-        let num_cols = unsafe { Highs_getNumCols(self.highs_ptr) };
-        let num_rows = unsafe { Highs_getNumRows(self.highs_ptr) };
-        let mut col_value: Vec<f64> = vec![0.; num_cols as usize];
-        let mut col_dual: Vec<f64> = vec![0.; num_cols as usize];
-        let mut row_value: Vec<f64> = vec![0.; num_rows as usize];
-        let mut row_dual: Vec<f64> = vec![0.; num_rows as usize];
-        unsafe {
-            Highs_getSolution(
-                self.highs_ptr,
-                col_value.as_mut_ptr(),
-                col_dual.as_mut_ptr(),
-                row_value.as_mut_ptr(),
-                row_dual.as_mut_ptr(),
-            );
-        };
-        let col_value: Vec<_> = col_value.iter().map(|v| v.round() as u32).collect();
-        trace!("Solution values:\n\t{:?}", col_value);
-        col_value
+    /// Adds a row excluding the buildings rented in `col_values`, and any superset of them,
+    /// from later solutions.
+    ///
+    /// The root is in every solution so it's left out; `col_values` must rent a building.
+    pub fn exclude_building_supersets(&mut self, col_values: &[u32]) {
+        // sum(item_flags set in col_values) <= count(set) - 1
+        let aindex: Vec<i32> = self
+            .item_flags
+            .values()
+            .filter(|flag| **flag != self.root_flag && col_values[**flag as usize] == 1)
+            .copied()
+            .collect();
+        debug_assert!(
+            !aindex.is_empty(),
+            "every chain is a superset of no buildings"
+        );
+        let avalue = vec![1.0; aindex.len()];
+        let highs_inf = self.highs.infinity();
+        self.highs
+            .add_row(-highs_inf, aindex.len() as f64 - 1.0, &aindex, &avalue);
     }
-}
 
-impl Drop for SubsetModel {
-    fn drop(&mut self) {
-        unsafe {
-            Highs_destroy(self.highs_ptr);
-        }
+    pub fn solution_col_values(&self) -> Vec<u32> {
+        self.highs.solution_col_values()
     }
 }
 
@@ -354,64 +411,194 @@ pub(crate) fn optimize(cli: &mut Cli) -> Result<()> {
     } else {
         get_region_buildings(Some(region_name))?
     };
-    let mut all_chains_by_region: IndexMap<usize, IndexMap<usize, Vec<Chain>>> = IndexMap::new();
+    let selection = BuildingSelection::from_cli(cli)?;
 
-    for (region_name, region_buildings) in all_region_buildings.iter() {
-        cli.region = Some(region_name.to_owned());
-        let region = RegionNodes::new(region_buildings)?;
-
-        if !cli.verbose.is_silent() {
-            trace!("Buildings");
-            region_buildings.iter().for_each(|b| trace!("{:#?}", b));
-            print_region_specs(&region);
-            print_starting_status(&region);
-        }
+    let cli: &Cli = cli;
+    let results = for_each_region(cli, &all_region_buildings, |region_name, buildings| {
+        optimize_region(cli, region_name, buildings, &selection)
+    });
 
-        info!("optimizing...");
-        let mut chains: ChainVec = (0..=region.max_worker_count)
-            .into_par_iter()
-            .map(|state_2_lb| optimize_worker(cli.clone(), region.clone(), state_2_lb))
-            .flatten()
-            .collect();
-        info!("Captured chain count: {:?}", chains.len());
-        info!("retaining...");
-        retain_dominating(&mut chains);
-        info!("Retained chain count: {:?}", chains.len());
-
-        info!("writing region-specific chains...");
-
-        write_chains(cli, &region, &mut chains)?;
-
-        if all_regions && !cli.for_validation {
-            let region_id = chains[0].indices[0];
-            for chain in chains {
-                let lodging = chain.worker_count;
-                all_chains_by_region
-                    .entry(region_id)
-                    .or_default()
-                    .entry(lodging)
-                    .or_default()
-                    .push(chain);
+    if all_regions && !cli.for_validation && selection.is_empty() {
+        if results.iter().any(|(_, result)| result.is_err()) {
+            warn!("Not writing all regions' chains since some regions failed.");
+        } else {
+            // Write the aggregated chains to all_lodging_storage.json
+            info!("writing all regions' chains...");
+            let mut all_chains_by_region: IndexMap<usize, IndexMap<usize, Vec<Chain>>> =
+                IndexMap::new();
+            for (_, chains) in results.iter() {
+                let chains = chains.as_ref().unwrap();
+                let region_id = chains[0].indices[0];
+                for chain in chains.iter() {
+                    let lodging = chain.worker_count;
+                    all_chains_by_region
+                        .entry(region_id)
+                        .or_default()
+                        .entry(lodging)
+                        .or_default()
+                        .push(chain.clone());
+                }
             }
+            all_chains_by_region.sort_keys();
+            write_all_chains(cli, &all_chains_by_region)?;
+            build_storage_index(cli)?;
         }
     }
+    check_region_results(&results)
+}
 
-    if all_regions && !cli.for_validation {
-        // Write the aggregated chains to all_lodging_storage.json
-        info!("writing all regions' chains...");
-        all_chains_by_region.sort_keys();
-        if all_regions {
-            write_all_chains(cli, &all_chains_by_region)?;
+fn optimize_region(
+    cli: &Cli,
+    region_name: &str,
+    region_buildings: &BuildingMap,
+    selection: &BuildingSelection,
+) -> Result<ChainVec> {
+    let mut cli = cli.clone();
+    cli.region = Some(region_name.to_owned());
+    let region = RegionNodes::new(region_buildings)?;
+    let region_selection = selection.for_region(region_buildings)?;
+
+    if !cli.verbose.is_silent() {
+        trace!("Buildings");
+        region_buildings.iter().for_each(|b| trace!("{:#?}", b));
+        print_region_specs(&region);
+        print_starting_status(&region);
+    }
+
+    info!("optimizing {}...", region_name);
+    let mut chains: ChainVec = (0..=region.max_worker_count)
+        .into_par_iter()
+        .map(|state_2_lb| {
+            optimize_worker(cli.clone(), region.clone(), &region_selection, state_2_lb)
+        })
+        .flatten()
+        .collect();
+    info!("{} captured chain count: {:?}", region_name, chains.len());
+    info!("retaining {}...", region_name);
+    retain_dominating(&mut chains);
+    info!("{} retained chain count: {:?}", region_name, chains.len());
+
+    info!("writing {} chains...", region_name);
+    write_chains(
+        &cli,
+        &region,
+        region_buildings,
+        &mut chains,
+        &region_selection,
+    )?;
+    Ok(chains)
+}
+
+/// Returns the cheapest chain with at least `storage` and `lodging` within the region
+/// `selection` or `None` when the region can't provide them.
+pub(crate) fn optimize_point(
+    region: &RegionNodes,
+    storage: usize,
+    lodging: usize,
+    selection: &BuildingSelection,
+) -> Option<list_buildings::Chain> {
+    let mut highs = SubsetModel::new(region, 0);
+    highs.fix_selection(selection);
+    let (state_1_sum_row, state_2_sum_row) = highs.state_sum_rows();
+    let status = unsafe {
+        let highs_inf = Highs_getInfinity(highs.mut_ptr());
+        Highs_changeRowBounds(highs.mut_ptr(), state_1_sum_row, storage as f64, highs_inf);
+        Highs_changeRowBounds(highs.mut_ptr(), state_2_sum_row, lodging as f64, highs_inf);
+        Highs_run(highs.mut_ptr());
+        Highs_getModelStatus(highs.mut_ptr())
+    };
+    if status != MODEL_STATUS_OPTIMAL {
+        return None;
+    }
+    let chain = Chain::from_highs(&highs, region);
+    Some(list_buildings::Chain {
+        lodging: chain.worker_count as u16,
+        storage: chain.warehouse_count as u16,
+        cost: chain.cost as u16,
+        indices: chain.indices.iter().map(|j| region.children[*j]).collect(),
+        states: chain.states,
+        alternatives: vec![],
+    })
+}
+
+/// Returns up to `limit` chains with at least `storage` and `lodging` costing no more than
+/// `within` CP over the cheapest within the region `selection`, ranked by cost and then
+/// building count.
+///
+/// Each solve excludes the building sets already found along with their supersets, so every
+/// chain uses a building set none of the cheaper chains fit within.
+pub(crate) fn optimize_within(
+    region: &RegionNodes,
+    storage: usize,
+    lodging: usize,
+    within: usize,
+    limit: usize,
+    selection: &BuildingSelection,
+) -> Vec<list_buildings::Chain> {
+    let mut highs = SubsetModel::new(region, 0);
+    highs.fix_selection(selection);
+    let (state_1_sum_row, state_2_sum_row) = highs.state_sum_rows();
+    unsafe {
+        let highs_inf = Highs_getInfinity(highs.mut_ptr());
+        Highs_changeRowBounds(highs.mut_ptr(), state_1_sum_row, storage as f64, highs_inf);
+        Highs_changeRowBounds(highs.mut_ptr(), state_2_sum_row, lodging as f64, highs_inf);
+    }
+
+    let mut chains: Vec<Chain> = vec![];
+    while chains.len() < limit {
+        let status = unsafe {
+            Highs_run(highs.mut_ptr());
+            Highs_getModelStatus(highs.mut_ptr())
+        };
+        if status != MODEL_STATUS_OPTIMAL {
+            break;
+        }
+        let chain = Chain::from_highs(&highs, region);
+        if chains
+            .first()
+            .is_some_and(|cheapest| chain.cost > cheapest.cost + within)
+        {
+            break;
+        }
+        // Every other chain rents a superset of no buildings.
+        let is_empty = chain.indices.len() == 1;
+        if !is_empty {
+            highs.exclude_building_supersets(&highs.solution_col_values());
+        }
+        chains.push(chain);
+        if is_empty {
+            break;
         }
     }
-    Ok(())
+    debug!("Found {} chains within {} CP.", chains.len(), within);
+
+    chains.sort_by_key(|chain| (chain.cost, chain.indices.len()));
+    chains
+        .into_iter()
+        .map(|chain| list_buildings::Chain {
+            lodging: chain.worker_count as u16,
+            storage: chain.warehouse_count as u16,
+            cost: chain.cost as u16,
+            indices: chain.indices.iter().map(|j| region.children[*j]).collect(),
+            states: chain.states,
+            alternatives: vec![],
+        })
+        .collect()
 }
 
-fn optimize_worker(cli: Cli, region: RegionNodes, state_2_sum_lb: usize) -> ChainVec {
+fn optimize_worker(
+    cli: Cli,
+    region: RegionNodes,
+    selection: &BuildingSelection,
+    state_2_sum_lb: usize,
+) -> ChainVec {
     let mut highs = SubsetModel::new(&region, state_2_sum_lb);
+    highs.fix_selection(selection);
+    if let Some(tiebreak) = cli.tiebreak {
+        highs.set_tiebreak(&region, tiebreak);
+    }
     let highs_inf = unsafe { Highs_getInfinity(highs.mut_ptr()) };
-    let state_1_sum_row: HighsInt = unsafe { Highs_getNumRows(highs.mut_ptr()) - 2 };
-    let state_2_sum_row: HighsInt = state_1_sum_row + 1;
+    let (state_1_sum_row, state_2_sum_row) = highs.state_sum_rows();
 
     info!("START: Job {state_2_sum_lb}");
     debug!("state lb rows: [{state_1_sum_row}, {state_2_sum_row}]");
@@ -447,8 +634,17 @@ fn optimize_worker(cli: Cli, region: RegionNodes, state_2_sum_lb: usize) -> Chai
             Highs_getModelStatus(highs.mut_ptr())
         };
         if status == MODEL_STATUS_OPTIMAL {
-            let chain = Chain::from_highs(&highs, &region);
+            let mut chain = Chain::from_highs(&highs, &region);
             state_1_sum_lb = chain.warehouse_count + 1;
+            if cli.tie_cap() > 0 {
+                record_ties(
+                    &mut highs,
+                    &region,
+                    &mut chain,
+                    state_2_sum_lb,
+                    cli.tie_cap(),
+                );
+            }
             chains.push(chain);
         } else {
             break;
@@ -462,6 +658,63 @@ fn optimize_worker(cli: Cli, region: RegionNodes, state_2_sum_lb: usize) -> Chai
     chains
 }
 
+fn record_ties(
+    highs: &mut SubsetModel,
+    region: &RegionNodes,
+    chain: &mut Chain,
+    state_2_sum_lb: usize,
+    tie_cap: usize,
+) {
+    // Pin the chain's storage and lodging and cut off each solution found until the cost rises.
+    let (state_1_sum_row, state_2_sum_row) = highs.state_sum_rows();
+    let highs_inf = unsafe { Highs_getInfinity(highs.mut_ptr()) };
+    let first_cut_row = unsafe { Highs_getNumRows(highs.mut_ptr()) };
+    let storage = chain.warehouse_count as f64;
+    let lodging = chain.worker_count as f64;
+    let mut col_values = unsafe {
+        Highs_changeRowBounds(highs.mut_ptr(), state_1_sum_row, storage, storage);
+        Highs_changeRowBounds(highs.mut_ptr(), state_2_sum_row, lodging, lodging);
+        highs.solution_col_values()
+    };
+
+    while chain.alternatives.len() < tie_cap {
+        highs.add_no_good_cut(&col_values);
+        let status = unsafe {
+            Highs_run(highs.mut_ptr());
+            Highs_getModelStatus(highs.mut_ptr())
+        };
+        if status != MODEL_STATUS_OPTIMAL {
+            break;
+        }
+        let tie = Chain::from_highs(highs, region);
+        if tie.cost != chain.cost {
+            break;
+        }
+        col_values = highs.solution_col_values();
+        chain.alternatives.push(Alternative {
+            indices: tie.indices,
+            states: tie.states,
+        });
+    }
+    debug!(
+        "S{} L{} has {} recorded ties.",
+        chain.warehouse_count,
+        chain.worker_count,
+        chain.alternatives.len()
+    );
+
+    unsafe {
+        let last_cut_row = Highs_getNumRows(highs.mut_ptr()) - 1;
+        Highs_deleteRowsByRange(highs.mut_ptr(), first_cut_row, last_cut_row);
+        Highs_changeRowBounds(
+            highs.mut_ptr(),
+            state_2_sum_row,
+            state_2_sum_lb as f64,
+            highs_inf,
+        );
+    }
+}
+
 fn print_region_specs(region: &RegionNodes) {
     debug!(
         "Region node parameters...\
@@ -490,12 +743,14 @@ fn print_starting_status(region: &RegionNodes) {
         &region.children,
         &region.states,
     );
+    // Regions are optimized in parallel so the status is written at once.
+    let name = &region.region_name;
     println!(
-        "Optimizing values for {} consisting of {} buildings in {} chains with {} storage/lodging combinations",
-        region.region_name, region.buildings.len(), building_chain_count, multistate_count
-    );
-    println!(
-        "With a maximum cost of {} with {} lodging and {} storage (out of {:?} possible).",
+        "Optimizing values for {name} consisting of {} buildings in {} chains with {} storage/lodging combinations\n\
+        {name}: With a maximum cost of {} with {} lodging and {} storage (out of {:?} possible).",
+        region.buildings.len(),
+        building_chain_count,
+        multistate_count,
         region.usage_counts.cost,
         region.usage_counts.worker_count,
         region.usage_counts.warehouse_count,
@@ -503,26 +758,42 @@ fn print_starting_status(region: &RegionNodes) {
     );
 }
 
-fn write_chains(cli: &Cli, region: &RegionNodes, chains: &mut Vec<Chain>) -> Result<()> {
+fn write_chains(
+    cli: &Cli,
+    region: &RegionNodes,
+    region_buildings: &BuildingMap,
+    chains: &mut Vec<Chain>,
+    selection: &BuildingSelection,
+) -> Result<()> {
     if cli.for_validation {
         for chain in chains.iter_mut() {
             chain.indices.clear();
             chain.states.clear();
+            chain.alternatives.clear();
         }
     } else {
         for chain in chains.iter_mut() {
             chain.indices = chain.indices.iter().map(|j| region.children[*j]).collect();
+            for alternative in chain.alternatives.iter_mut() {
+                alternative.indices = alternative
+                    .indices
+                    .iter()
+                    .map(|j| region.children[*j])
+                    .collect();
+            }
         }
     }
 
     let region_name = cli.region.clone().unwrap();
-    let file_name = region_name.replace(' ', "_");
     let path = if cli.for_validation {
-        format!("./data/housecraft/validation/HiGHS/{}.json", file_name)
+        let file_name = region_name.replace(' ', "_");
+        PathBuf::from(format!(
+            "./data/housecraft/validation/HiGHS/{}.json",
+            file_name
+        ))
     } else {
-        format!("./data/housecraft/{}.json", file_name)
+        chains_path(&region_name, selection)
     };
-    let path = PathBuf::from(path);
     fs::create_dir_all(path.parent().unwrap())?;
     let mut output = File::create(path.clone())?;
 
@@ -534,6 +805,9 @@ fn write_chains(cli: &Cli, region: &RegionNodes, chains: &mut Vec<Chain>) -> Res
         })
         .to_string();
     output.write_all(json.as_bytes())?;
+    if !cli.for_validation {
+        write_fingerprint(&path, region_buildings)?;
+    }
 
     if !cli.verbose.is_silent() {
         println!(
@@ -0,0 +1,369 @@
+//! Upgrade an existing set of owned buildings to reach storage/lodging targets.
+//!
+//! Owned buildings are fixed as rented in the region's `SubsetModel` with no CP cost, so the
+//! solve only pays for the additional rentals. Unless `--lock-owned` is given an owned building
+//! may switch usage; a small objective penalty, solved to a zero gap, keeps switches to those
+//! actually needed. Owned buildings used for anything else (a workshop, say) may be left out of
+//! the state sums.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{bail, Ok, Result};
+use comfy_table::{Attribute, Cell, Table};
+use highs_sys::*;
+
+use crate::cli_args::Cli;
+use crate::houseinfo::*;
+use crate::name_resolution::resolve_building_key;
+use crate::optimize::SubsetModel;
+use crate::region_nodes::RegionNodes;
+
+// Objective penalty for changing an owned building's usage; well below the 1 CP minimum cost.
+const SWITCH_PENALTY: f64 = 0.001;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum OwnedState {
+    Storage,
+    Lodging,
+    Other,
+}
+
+impl OwnedState {
+    pub(crate) fn parse(text: &str) -> Result<Self> {
+        match text.trim().to_lowercase().as_str() {
+            "s" | "storage" | "1" => Ok(OwnedState::Storage),
+            "l" | "lodging" | "2" => Ok(OwnedState::Lodging),
+            "o" | "other" | "w" | "workshop" => Ok(OwnedState::Other),
+            _ => bail!(
+                "Unknown usage state '{}'.\n Use storage, lodging or other.",
+                text.trim()
+            ),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            OwnedState::Storage => "storage",
+            OwnedState::Lodging => "lodging",
+            OwnedState::Other => "other",
+        }
+    }
+}
+
+/// Returns the owned building keys and states from `key:state,...` or a file of `key:state`
+/// lines. Buildings may be given by key, name or unique name prefix.
+pub(crate) fn parse_owned(
+    buildings: &BuildingMap,
+    owned: &str,
+) -> Result<Vec<(usize, OwnedState)>> {
+    // Names such as "Calpheon South Gate 2, 1F" contain commas so files are split by line only.
+    let entries: Vec<String> = if Path::new(owned).is_file() {
+        fs::read_to_string(owned)?
+            .lines()
+            .map(|line| line.split('#').next().unwrap().trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect()
+    } else {
+        owned
+            .split(',')
+            .map(|entry| entry.trim().to_string())
+            .filter(|entry| !entry.is_empty())
+            .collect()
+    };
+
+    let mut owned_states = vec![];
+    for entry in entries.iter() {
+        let Some((building, state)) = entry.rsplit_once(':') else {
+            bail!("Missing usage state in '{}'.\n Use 'key:state'.", entry);
+        };
+        let key = resolve_building_key(buildings.values(), building.trim())?;
+        let state = OwnedState::parse(state)?;
+        let building = buildings.get(&key).unwrap();
+        if state == OwnedState::Storage && building.warehouse_count == 0 {
+            bail!("{} ({}) offers no storage.", building.building_name, key);
+        }
+        if state == OwnedState::Lodging && building.worker_count == 0 {
+            bail!("{} ({}) offers no lodging.", building.building_name, key);
+        }
+        if owned_states.iter().any(|(k, _)| *k == key) {
+            bail!(
+                "{} ({}) is listed more than once.",
+                building.building_name,
+                key
+            );
+        }
+        owned_states.push((key, state));
+    }
+
+    for (key, _) in owned_states.iter() {
+        let building = buildings.get(key).unwrap();
+        if buildings.contains_key(&building.need_key)
+            && !owned_states.iter().any(|(k, _)| *k == building.need_key)
+        {
+            let parent = buildings.get(&building.need_key).unwrap();
+            bail!(
+                "{} ({}) requires {} ({}) which is not owned.",
+                building.building_name,
+                key,
+                parent.building_name,
+                parent.key
+            );
+        }
+    }
+    Ok(owned_states)
+}
+
+fn fix_owned(model: &mut SubsetModel, owned: &[(usize, OwnedState)], lock: bool) {
+    let highs = model.mut_ptr();
+    let highs_inf = unsafe { Highs_getInfinity(highs) };
+    for (key, state) in owned.iter() {
+        let item = *key as u32;
+        let item_flag = model.item_flags[&item];
+        let state_1_flag = model.state_1_flags[&item];
+        let state_2_flag = model.state_2_flags[&item];
+        unsafe {
+            // Already rented and paid for.
+            Highs_changeColCost(highs, item_flag, 0.0);
+            Highs_changeColBounds(highs, item_flag, 1.0, 1.0);
+
+            if *state == OwnedState::Other {
+                // state_1_flags[item] + state_2_flags[item] - item_flags[item] <= 0
+                Highs_changeRowBounds(highs, model.selection_rows[&item], -highs_inf, 0.0);
+            }
+            match (state, lock) {
+                (OwnedState::Storage, true) => {
+                    Highs_changeColBounds(highs, state_1_flag, 1.0, 1.0);
+                }
+                (OwnedState::Lodging, true) => {
+                    Highs_changeColBounds(highs, state_2_flag, 1.0, 1.0);
+                }
+                (OwnedState::Other, true) => {
+                    Highs_changeColBounds(highs, state_1_flag, 0.0, 0.0);
+                    Highs_changeColBounds(highs, state_2_flag, 0.0, 0.0);
+                }
+                (OwnedState::Storage, false) => {
+                    Highs_changeColCost(highs, state_2_flag, SWITCH_PENALTY);
+                }
+                (OwnedState::Lodging, false) => {
+                    Highs_changeColCost(highs, state_1_flag, SWITCH_PENALTY);
+                }
+                (OwnedState::Other, false) => {
+                    Highs_changeColCost(highs, state_1_flag, SWITCH_PENALTY);
+                    Highs_changeColCost(highs, state_2_flag, SWITCH_PENALTY);
+                }
+            }
+        }
+    }
+}
+
+fn print_upgrade(region: &RegionNodes, owned: &HashMap<usize, OwnedState>, col_values: &[u32]) {
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("Action").add_attribute(Attribute::Dim),
+        Cell::new("Usage").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut counts = UsageCounters::new();
+    let mut rentals = 0;
+    let mut switches = 0;
+    for (i, col) in col_values
+        .chunks_exact(3)
+        .take(region.num_nodes)
+        .enumerate()
+        .skip(1)
+    {
+        if col[0] != 1 {
+            continue;
+        }
+        let building = region.buildings.get(&region.children[i]).unwrap();
+        let state = if col[1] == 1 {
+            OwnedState::Storage
+        } else if col[2] == 1 {
+            OwnedState::Lodging
+        } else {
+            OwnedState::Other
+        };
+        let (action, usage, cost) = match owned.get(&building.key) {
+            Some(owned_state) if *owned_state == state => ("Keep", state.name().to_string(), 0),
+            Some(owned_state) => {
+                switches += 1;
+                let usage = format!("{} → {}", owned_state.name(), state.name());
+                ("Switch", usage, 0)
+            }
+            None => {
+                rentals += 1;
+                ("Rent", state.name().to_string(), building.cost)
+            }
+        };
+        let (warehouse_count, worker_count) = match state {
+            OwnedState::Storage => (building.warehouse_count.to_string(), "".to_string()),
+            OwnedState::Lodging => ("".to_string(), building.worker_count.to_string()),
+            OwnedState::Other => ("".to_string(), "".to_string()),
+        };
+        counts.cost += cost;
+        if state == OwnedState::Storage {
+            counts.warehouse_count += building.warehouse_count;
+        } else if state == OwnedState::Lodging {
+            counts.worker_count += building.worker_count;
+        }
+
+        let mut row = vec![
+            Cell::new(building.key),
+            Cell::new(&building.building_name),
+            Cell::new(action),
+            Cell::new(usage),
+            Cell::new(if cost > 0 {
+                cost.to_string()
+            } else {
+                "".to_string()
+            }),
+            Cell::new(warehouse_count),
+            Cell::new(worker_count),
+        ];
+        if action == "Keep" {
+            row.iter_mut()
+                .for_each(|cell| *cell = cell.clone().add_attribute(Attribute::Dim));
+        }
+        table.add_row(row);
+    }
+
+    table.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new(format!("{rentals} rentals, {switches} switches")).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new(counts.cost).add_attribute(Attribute::Bold),
+        Cell::new(counts.warehouse_count).add_attribute(Attribute::Bold),
+        Cell::new(counts.worker_count).add_attribute(Attribute::Bold),
+    ]);
+    println!("\n{table}");
+}
+
+/// Returns the solution columns of the cheapest upgrade of `owned` reaching `storage` and
+/// `lodging`, or `None` when no upgrade does.
+fn solve_upgrade(
+    region: &RegionNodes,
+    owned: &[(usize, OwnedState)],
+    lock: bool,
+    storage: u16,
+    lodging: u16,
+) -> Option<Vec<u32>> {
+    let mut model = SubsetModel::new(region, 0);
+    fix_owned(&mut model, owned, lock);
+    // The switch penalty is far below the default relative gap.
+    model.require_exact_optimum();
+
+    let (state_1_sum_row, state_2_sum_row) = model.state_sum_rows();
+    let status = unsafe {
+        let highs = model.mut_ptr();
+        let highs_inf = Highs_getInfinity(highs);
+        Highs_changeRowBounds(highs, state_1_sum_row, storage as f64, highs_inf);
+        Highs_changeRowBounds(highs, state_2_sum_row, lodging as f64, highs_inf);
+        Highs_run(highs);
+        Highs_getModelStatus(highs)
+    };
+    (status == MODEL_STATUS_OPTIMAL).then(|| model.solution_col_values())
+}
+
+/// Prints the cheapest additional rentals and usage switches reaching the -S/-L targets from
+/// the `--owned` buildings.
+pub(crate) fn upgrade_owned(cli: &Cli) -> Result<()> {
+    let region_buildings = get_region_buildings(cli.region.clone())?;
+    let (_, buildings) = region_buildings.first_key_value().unwrap();
+    let region = RegionNodes::new(buildings)?;
+    let owned = parse_owned(&region.buildings, cli.owned.as_ref().unwrap())?;
+
+    let storage = cli.storage.unwrap_or(0);
+    let lodging = cli.lodging.unwrap_or(0);
+    let Some(col_values) = solve_upgrade(&region, &owned, cli.lock_owned, storage, lodging) else {
+        bail!(
+            "No upgrade of the owned buildings reaches {} storage and {} lodging in {}.\n \
+            The maximum storage and lodging counts are {} and {}.",
+            storage,
+            lodging,
+            region.region_name,
+            region.max_warehouse_count,
+            region.max_worker_count
+        );
+    };
+
+    print_upgrade(&region, &owned.into_iter().collect(), &col_values);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn velia() -> RegionNodes {
+        let region = get_region_buildings(Some("Velia".to_string())).unwrap();
+        RegionNodes::new(region.values().next().unwrap()).unwrap()
+    }
+
+    /// Returns the CP paid and the owned buildings switched by `col_values`.
+    fn cost_and_switches(
+        region: &RegionNodes,
+        owned: &[(usize, OwnedState)],
+        col_values: &[u32],
+    ) -> (usize, usize) {
+        let (mut cost, mut switches) = (0, 0);
+        for (i, col) in col_values
+            .chunks_exact(3)
+            .take(region.num_nodes)
+            .enumerate()
+        {
+            if i == 0 || col[0] != 1 {
+                continue;
+            }
+            let key = region.children[i];
+            let state = match (col[1], col[2]) {
+                (1, _) => OwnedState::Storage,
+                (_, 1) => OwnedState::Lodging,
+                _ => OwnedState::Other,
+            };
+            match owned.iter().find(|(k, _)| *k == key) {
+                Some((_, owned_state)) if *owned_state != state => switches += 1,
+                Some(_) => {}
+                None => cost += region.buildings[&key].cost,
+            }
+        }
+        (cost, switches)
+    }
+
+    #[test]
+    fn switches_are_only_made_when_cheaper_than_keeping() {
+        let region = velia();
+        let owned = parse_owned(
+            &region.buildings,
+            "2111:lodging,2112:lodging,2113:storage,2125:lodging,2126:storage,2116:other",
+        )
+        .unwrap();
+        for lodging in 0..=region.max_worker_count as u16 {
+            for storage in (0..=region.max_warehouse_count as u16).step_by(4) {
+                let Some(free) = solve_upgrade(&region, &owned, false, storage, lodging) else {
+                    continue;
+                };
+                let (cost, switches) = cost_and_switches(&region, &owned, &free);
+                let locked = solve_upgrade(&region, &owned, true, storage, lodging)
+                    .map(|locked| cost_and_switches(&region, &owned, &locked));
+                if let Some((locked_cost, locked_switches)) = locked {
+                    assert_eq!(locked_switches, 0);
+                    assert!(cost <= locked_cost, "S{storage} L{lodging}");
+                    if cost == locked_cost {
+                        assert_eq!(switches, 0, "S{storage} L{lodging} switched for nothing");
+                    }
+                } else {
+                    assert!(switches > 0, "S{storage} L{lodging}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Find the cheapest building set covering a list of crafting usages.
+//!
+//! Each requested usage is assigned to a distinct building offering it at (at least) the requested
+//! level. Assigned buildings require all of their ancestors, using the same parent <- child
+//! requirement constraints as the storage/lodging `SubsetModel`, and the total CP of every rented
+//! building is minimized.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use anyhow::{bail, Ok, Result};
+use comfy_table::{Attribute, Cell, Table};
+use highs_sys::*;
+
+use crate::find_crafts::{craft_candidates, CraftQuery, LevelBound};
+use crate::highs_model::HighsModel;
+use crate::houseinfo::*;
+
+struct CraftPlanModel {
+    highs: HighsModel,
+    item_flags: BTreeMap<usize, i32>,
+    assignment_flags: Vec<Vec<(usize, i32)>>,
+}
+
+impl CraftPlanModel {
+    fn new(buildings: &BuildingMap, usage_buildings: &[Vec<usize>]) -> Self {
+        let mut model = CraftPlanModel {
+            highs: HighsModel::new(None),
+            item_flags: BTreeMap::new(),
+            assignment_flags: vec![],
+        };
+        model.initialize_item_flags(buildings, usage_buildings);
+        model.initialize_assignment_flags(usage_buildings);
+        model.initialize_item_requirement_constraints(buildings);
+        model.initialize_assignment_constraints();
+        model
+    }
+
+    fn initialize_item_flags(&mut self, buildings: &BuildingMap, usage_buildings: &[Vec<usize>]) {
+        // Only candidates and their ancestors can be part of a plan.
+        let mut keys = BTreeSet::new();
+        for key in usage_buildings.iter().flatten() {
+            keys.insert(*key);
+            keys.extend(building_ancestors(*key, buildings).iter().map(|b| b.key));
+        }
+        for key in keys {
+            let cost = buildings.get(&key).unwrap().cost as f64;
+            let column_id = self.highs.add_binary_col(cost);
+            self.item_flags.insert(key, column_id);
+        }
+    }
+
+    fn initialize_assignment_flags(&mut self, usage_buildings: &[Vec<usize>]) {
+        for keys in usage_buildings.iter() {
+            let mut flags = vec![];
+            for key in keys.iter() {
+                flags.push((*key, self.highs.add_binary_col(0.0)));
+            }
+            self.assignment_flags.push(flags);
+        }
+    }
+
+    fn initialize_item_requirement_constraints(&mut self, buildings: &BuildingMap) {
+        // item_flags[child] - item_flags[parent] <= 0
+        let highs_inf = self.highs.infinity();
+        let item_flags = self.item_flags.clone();
+        for (key, child_flag) in item_flags.iter() {
+            let parent = buildings.get(key).unwrap().need_key;
+            if let Some(parent_flag) = item_flags.get(&parent) {
+                self.highs
+                    .add_row(-highs_inf, 0.0, &[*child_flag, *parent_flag], &[1.0, -1.0]);
+            }
+        }
+    }
+
+    fn initialize_assignment_constraints(&mut self) {
+        let highs_inf = self.highs.infinity();
+        let mut building_assignments: HashMap<usize, Vec<i32>> = HashMap::new();
+
+        for flags in self.assignment_flags.clone().iter() {
+            // Each usage is assigned exactly one building: sum(assignment_flags[usage]) == 1
+            let aindex: Vec<i32> = flags.iter().map(|(_, flag)| *flag).collect();
+            let avalue = vec![1.0; aindex.len()];
+            self.highs.add_row(1.0, 1.0, &aindex, &avalue);
+
+            // Assigned buildings are rented: assignment_flag - item_flags[building] <= 0
+            for (key, flag) in flags.iter() {
+                let item_flag = self.item_flags[key];
+                self.highs
+                    .add_row(-highs_inf, 0.0, &[*flag, item_flag], &[1.0, -1.0]);
+                building_assignments.entry(*key).or_default().push(*flag);
+            }
+        }
+
+        // Each building serves at most one usage.
+        for flags in building_assignments.values().filter(|f| f.len() > 1) {
+            let avalue = vec![1.0; flags.len()];
+            self.highs.add_row(-highs_inf, 1.0, flags, &avalue);
+        }
+    }
+
+    fn solve(&mut self) -> Option<Vec<u32>> {
+        if self.highs.run() != MODEL_STATUS_OPTIMAL {
+            return None;
+        }
+        Some(self.highs.solution_col_values())
+    }
+}
+
+fn parse_usages(usages: &str) -> Result<Vec<CraftQuery>> {
+    // A bare level in a plan is a minimum since a building offering a higher level also works.
+    let mut queries = vec![];
+    for text in usages.split([',', '+']).filter(|s| !s.trim().is_empty()) {
+        let mut query = CraftQuery::parse(text)?;
+        if let LevelBound::Exactly(n) = query.level {
+            query.level = LevelBound::AtLeast(n);
+        }
+        queries.push(query);
+    }
+    if queries.is_empty() {
+        bail!("No crafting usages given.\n Use '--list-crafts'.");
+    }
+    Ok(queries)
+}
+
+fn print_plan(
+    queries: &[CraftQuery],
+    buildings: &BuildingMap,
+    rented: &BTreeSet<usize>,
+    assigned: &HashMap<usize, usize>,
+) {
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Region").add_attribute(Attribute::Dim),
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("Usage").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut rented: Vec<_> = rented.iter().map(|k| buildings.get(k).unwrap()).collect();
+    rented.sort_by(|a, b| (&a.region_name, a.key).cmp(&(&b.region_name, b.key)));
+    let mut total_cost = 0;
+    for building in rented.iter() {
+        let usage = assigned
+            .get(&building.key)
+            .map(|u| queries[*u].text.clone())
+            .unwrap_or_else(|| "(prerequisite)".to_string());
+        total_cost += building.cost;
+        table.add_row(vec![
+            &building.region_name,
+            &building.key.to_string(),
+            &building.building_name,
+            &usage,
+            &building.cost.to_string(),
+        ]);
+    }
+    table.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new(format!("{} buildings", rented.len())).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new(total_cost).add_attribute(Attribute::Bold),
+    ]);
+    println!("\n{table}");
+}
+
+/// Prints the minimum CP building set with one distinct building per requested usage.
+pub(crate) fn plan_crafts(region: Option<String>, usages: String) -> Result<()> {
+    let regions_buildings = get_region_buildings(region)?;
+    let queries = parse_usages(&usages)?;
+
+    let buildings: BuildingMap = regions_buildings
+        .values()
+        .flat_map(|map| map.iter().map(|(k, b)| (*k, b.clone())))
+        .collect();
+    let mut usage_buildings = vec![];
+    for query in queries.iter() {
+        let keys: BTreeSet<usize> = craft_candidates(&regions_buildings, query)
+            .iter()
+            .map(|c| c.building.key)
+            .collect();
+        if keys.is_empty() {
+            bail!(
+                "No buildings offer '{}'.\n Use '--list-crafts'.",
+                query.text
+            );
+        }
+        usage_buildings.push(keys.into_iter().collect::<Vec<_>>());
+    }
+
+    let mut model = CraftPlanModel::new(&buildings, &usage_buildings);
+    let Some(col_values) = model.solve() else {
+        bail!("No set of distinct buildings covers '{}'.", usages);
+    };
+
+    let rented: BTreeSet<usize> = model
+        .item_flags
+        .iter()
+        .filter(|(_, flag)| col_values[**flag as usize] == 1)
+        .map(|(key, _)| *key)
+        .collect();
+    let mut assigned = HashMap::new();
+    for (usage, flags) in model.assignment_flags.iter().enumerate() {
+        for (key, flag) in flags.iter() {
+            if col_values[*flag as usize] == 1 {
+                assigned.insert(*key, usage);
+            }
+        }
+    }
+
+    print_plan(&queries, &buildings, &rented, &assigned);
+    Ok(())
+}
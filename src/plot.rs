@@ -0,0 +1,248 @@
+//! Terminal and SVG charts of a region's chains.
+//!
+//! Each curve is the cheapest CP reaching a storage count with at least one lodging level,
+//! read from the region's chain file. Regions with many lodging levels are thinned to
+//! `MAX_CURVES` evenly spaced levels including the lowest and highest.
+
+use std::fmt::Write as _;
+use std::fs;
+
+use anyhow::{Ok, Result};
+
+use crate::cli_args::Cli;
+use crate::houseinfo::*;
+use crate::list_buildings::Chain;
+use crate::selection::BuildingSelection;
+
+const MAX_CURVES: usize = 8;
+const PLOT_WIDTH: usize = 64;
+const PLOT_HEIGHT: usize = 20;
+const MARKERS: [char; MAX_CURVES] = ['0', '1', '2', '3', '4', '5', '6', '7'];
+
+const SVG_WIDTH: f64 = 720.0;
+const SVG_HEIGHT: f64 = 440.0;
+const SVG_MARGIN: f64 = 56.0;
+
+struct Curve {
+    lodging: u16,
+    /// The cheapest cost for each storage count, `None` when out of reach at this lodging.
+    costs: Vec<Option<u16>>,
+}
+
+fn lodging_levels(chains: &[Chain]) -> Vec<u16> {
+    let mut levels: Vec<u16> = chains.iter().map(|chain| chain.lodging).collect();
+    levels.sort_unstable();
+    levels.dedup();
+    if levels.len() <= MAX_CURVES {
+        return levels;
+    }
+    let last = levels.len() - 1;
+    (0..MAX_CURVES)
+        .map(|i| levels[i * last / (MAX_CURVES - 1)])
+        .collect()
+}
+
+fn front_curves(chains: &[Chain]) -> Vec<Curve> {
+    let max_storage = chains.iter().map(|chain| chain.storage).max().unwrap_or(0);
+    lodging_levels(chains)
+        .into_iter()
+        .map(|lodging| {
+            let costs = (0..=max_storage)
+                .map(|storage| {
+                    chains
+                        .iter()
+                        .filter(|chain| chain.storage >= storage && chain.lodging >= lodging)
+                        .map(|chain| chain.cost)
+                        .min()
+                })
+                .collect();
+            Curve { lodging, costs }
+        })
+        .collect()
+}
+
+fn render_chart(region_name: &str, curves: &[Curve]) -> String {
+    let max_storage = curves[0].costs.len() - 1;
+    let max_cost = curves
+        .iter()
+        .flat_map(|curve| curve.costs.iter().flatten())
+        .max()
+        .copied()
+        .unwrap_or(0)
+        .max(1) as usize;
+    let width = (max_storage + 1).min(PLOT_WIDTH);
+
+    let mut grid = vec![vec![' '; width]; PLOT_HEIGHT];
+    for (curve, marker) in curves.iter().zip(MARKERS.iter()) {
+        let columns = (0..width).map(|column| (column, column * max_storage / (width - 1).max(1)));
+        for (column, storage) in columns {
+            if let Some(cost) = curve.costs[storage] {
+                let y = (cost as usize * (PLOT_HEIGHT - 1) + max_cost / 2) / max_cost;
+                grid[PLOT_HEIGHT - 1 - y][column] = *marker;
+            }
+        }
+    }
+
+    let mut chart = format!("\n{region_name} CP by storage for each lodging level\n\n");
+    for (row, cells) in grid.iter().enumerate() {
+        let line: String = cells.iter().collect();
+        if row % 5 == 0 || row == PLOT_HEIGHT - 1 {
+            let cost =
+                ((PLOT_HEIGHT - 1 - row) * max_cost + (PLOT_HEIGHT - 1) / 2) / (PLOT_HEIGHT - 1);
+            let _ = writeln!(chart, "{cost:>5} ┤{line}");
+        } else {
+            let _ = writeln!(chart, "      │{line}");
+        }
+    }
+    let _ = writeln!(chart, "      └{}", "─".repeat(width));
+    let max_label = max_storage.to_string();
+    let _ = writeln!(
+        chart,
+        "       0{:>pad$}",
+        max_label,
+        pad = width.saturating_sub(1)
+    );
+    let _ = writeln!(chart, "{:>pad$}", "storage", pad = 7 + width / 2 + 3);
+    let legend: Vec<String> = curves
+        .iter()
+        .zip(MARKERS.iter())
+        .map(|(curve, marker)| format!("{marker} = L{}", curve.lodging))
+        .collect();
+    let _ = writeln!(chart, "\n  {}", legend.join("   "));
+    chart
+}
+
+fn nice_step(max_value: usize) -> usize {
+    let raw = (max_value / 8).max(1);
+    let magnitude = 10usize.pow((raw as f64).log10().floor() as u32);
+    [1, 2, 5, 10]
+        .iter()
+        .map(|m| m * magnitude)
+        .find(|step| *step >= raw)
+        .unwrap()
+}
+
+fn render_svg(region_name: &str, curves: &[Curve]) -> String {
+    let max_storage = (curves[0].costs.len() - 1).max(1);
+    let max_cost = curves
+        .iter()
+        .flat_map(|curve| curve.costs.iter().flatten())
+        .max()
+        .copied()
+        .unwrap_or(0)
+        .max(1) as usize;
+    let plot_width = SVG_WIDTH - 2.0 * SVG_MARGIN;
+    let plot_height = SVG_HEIGHT - 2.0 * SVG_MARGIN;
+    let x = |storage: usize| SVG_MARGIN + storage as f64 / max_storage as f64 * plot_width;
+    let y = |cost: usize| SVG_MARGIN + plot_height - cost as f64 / max_cost as f64 * plot_height;
+
+    let mut svg = String::new();
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{SVG_WIDTH}" height="{SVG_HEIGHT}" font-family="sans-serif" font-size="12">"#
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" fill="white"/>
+<text x="{}" y="24" text-anchor="middle" font-size="15">{region_name} CP by storage for each lodging level</text>"#,
+        SVG_WIDTH / 2.0
+    );
+
+    // Axes, grid lines and tick labels.
+    let _ = writeln!(
+        svg,
+        r#"<path d="M{left} {top} V{bottom} H{right}" stroke="black" fill="none"/>"#,
+        left = x(0),
+        top = y(max_cost),
+        bottom = y(0),
+        right = x(max_storage)
+    );
+    let step = nice_step(max_storage);
+    for storage in (0..=max_storage).step_by(step) {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{x}" y1="{top}" x2="{x}" y2="{bottom}" stroke="#e0e0e0"/>
+<text x="{x}" y="{label}" text-anchor="middle">{storage}</text>"##,
+            x = x(storage),
+            top = y(max_cost),
+            bottom = y(0),
+            label = y(0) + 16.0
+        );
+    }
+    let step = nice_step(max_cost);
+    for cost in (0..=max_cost).step_by(step) {
+        let _ = writeln!(
+            svg,
+            r##"<line x1="{left}" y1="{y}" x2="{right}" y2="{y}" stroke="#e0e0e0"/>
+<text x="{label}" y="{y}" text-anchor="end" dominant-baseline="middle">{cost}</text>"##,
+            left = x(0),
+            right = x(max_storage),
+            y = y(cost),
+            label = x(0) - 6.0
+        );
+    }
+    let _ = writeln!(
+        svg,
+        r#"<text x="{}" y="{}" text-anchor="middle">Storage</text>
+<text x="16" y="{}" text-anchor="middle" transform="rotate(-90 16 {})">CP</text>"#,
+        SVG_MARGIN + plot_width / 2.0,
+        SVG_HEIGHT - 12.0,
+        SVG_MARGIN + plot_height / 2.0,
+        SVG_MARGIN + plot_height / 2.0
+    );
+
+    // One stepped curve per lodging level with its legend entry.
+    for (i, curve) in curves.iter().enumerate() {
+        let hue = i * 360 / curves.len();
+        let mut path = String::new();
+        let mut previous: Option<u16> = None;
+        let mut last_storage = 0;
+        for (storage, cost) in curve.costs.iter().enumerate() {
+            let Some(cost) = cost else {
+                continue;
+            };
+            last_storage = storage;
+            match previous {
+                None => {
+                    let _ = write!(path, "M{} {}", x(storage), y(*cost as usize));
+                }
+                Some(previous) if previous != *cost => {
+                    let _ = write!(path, " H{} V{}", x(storage), y(*cost as usize));
+                }
+                _ => {}
+            }
+            previous = Some(*cost);
+        }
+        let _ = write!(path, " H{}", x(last_storage));
+        let _ = writeln!(
+            svg,
+            r#"<path d="{path}" stroke="hsl({hue}, 70%, 40%)" stroke-width="2" fill="none"/>
+<rect x="{lx}" y="{ly}" width="12" height="3" fill="hsl({hue}, 70%, 40%)"/>
+<text x="{tx}" y="{ty}" dominant-baseline="middle">L{lodging}</text>"#,
+            lx = SVG_WIDTH - SVG_MARGIN + 8.0,
+            ly = SVG_MARGIN + i as f64 * 18.0,
+            tx = SVG_WIDTH - SVG_MARGIN + 24.0,
+            ty = SVG_MARGIN + i as f64 * 18.0 + 2.0,
+            lodging = curve.lodging
+        );
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Prints the region's CP-by-storage curves for each lodging level and writes them as an SVG
+/// when `--svg` is given.
+pub(crate) fn plot(cli: &Cli) -> Result<()> {
+    let region_buildings = get_region_buildings(cli.region.clone())?;
+    let (region_name, buildings) = region_buildings.first_key_value().unwrap();
+    let selection = BuildingSelection::from_cli(cli)?.for_region(buildings)?;
+    let chains = Chain::many_from_selection_json(region_name, &selection)?;
+    let curves = front_curves(&chains);
+
+    print!("{}", render_chart(region_name, &curves));
+    if let Some(path) = &cli.svg {
+        fs::write(path, render_svg(region_name, &curves))?;
+        println!("\nSVG written to {path}.");
+    }
+    Ok(())
+}
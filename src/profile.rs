@@ -0,0 +1,322 @@
+//! Family housing profiles.
+//!
+//! A profile records what is actually rented: per region, each building (key or name) with its
+//! usage, e.g.
+//!
+//! ```json
+//! {
+//!   "Velia": [
+//!     { "building": 2116, "usage": "Storage" },
+//!     { "building": "Velia 3-1", "usage": "Residence" }
+//!   ]
+//! }
+//! ```
+//!
+//! Usages are crafting usages as listed by `--list-crafts` with an optional level.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::BufReader;
+
+use anyhow::{bail, Context, Result};
+use comfy_table::{Attribute, Cell, Table};
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+use crate::find_crafts::CraftQuery;
+use crate::houseinfo::*;
+use crate::list_buildings::Chain;
+use crate::name_resolution::{normalize_name, resolve_building_key, resolve_region_name};
+use crate::owned::OwnedState;
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum BuildingRef {
+    Key(usize),
+    Name(String),
+}
+
+#[derive(Debug, Deserialize)]
+struct ProfileEntry {
+    building: BuildingRef,
+    usage: String,
+}
+
+type ProfileFile = IndexMap<String, Vec<ProfileEntry>>;
+
+/// A validated region profile with each building's usage label and state.
+#[derive(Clone, Debug)]
+pub(crate) struct RegionProfile {
+    pub region_name: String,
+    pub buildings: Vec<(usize, String, OwnedState)>,
+    pub counts: UsageCounters,
+    pub housing_cost: usize,
+}
+
+fn read_profile(path: &str) -> Result<ProfileFile> {
+    let file = File::open(path).with_context(|| format!("Can't find {path}"))?;
+    let reader = BufReader::new(file);
+    let profile = serde_json::from_reader(reader)
+        .with_context(|| format!("Unable to read {path} as a housing profile"))?;
+    Ok(profile)
+}
+
+fn validate_usage(building: &Building, usage: &str) -> Result<(String, OwnedState)> {
+    let query = CraftQuery::parse(usage)?;
+    let mut matches: Vec<_> = building
+        .craft_list
+        .iter()
+        .filter(|craft| query.matches(craft))
+        .collect();
+    // Prefer the exact usage so "Storage" isn't taken as "Guild Storage".
+    matches.sort_by_key(|craft| {
+        normalize_name(CRAFT_USAGE.get(&craft.item_craft_index).unwrap()) != query.usage
+    });
+    let Some(craft) = matches.first() else {
+        bail!(
+            "{} ({}) doesn't offer '{}'.\n Use '--show-building {}'.",
+            building.building_name,
+            building.key,
+            usage,
+            building.key
+        );
+    };
+    let state = match craft.item_craft_index {
+        1 => OwnedState::Lodging,
+        2 => OwnedState::Storage,
+        _ => OwnedState::Other,
+    };
+    let label = format!(
+        "{} {}",
+        CRAFT_USAGE.get(&craft.item_craft_index).unwrap(),
+        craft.house_level
+    );
+    Ok((label, state))
+}
+
+fn validate_region(
+    region_name: &str,
+    buildings: &BuildingMap,
+    entries: &[ProfileEntry],
+    problems: &mut Vec<String>,
+) -> RegionProfile {
+    let mut profile = RegionProfile {
+        region_name: region_name.to_owned(),
+        buildings: vec![],
+        counts: UsageCounters::new(),
+        housing_cost: 0,
+    };
+
+    for entry in entries.iter() {
+        let query = match &entry.building {
+            BuildingRef::Key(key) => key.to_string(),
+            BuildingRef::Name(name) => name.clone(),
+        };
+        let key = match resolve_building_key(buildings.values(), &query) {
+            Ok(key) => key,
+            Err(e) => {
+                problems.push(format!("{region_name}: {e:#}").replace('\n', "\n  "));
+                continue;
+            }
+        };
+        let building = buildings.get(&key).unwrap();
+        if profile.buildings.iter().any(|(k, _, _)| *k == key) {
+            problems.push(format!(
+                "{region_name}: {} ({key}) is listed more than once.",
+                building.building_name
+            ));
+            continue;
+        }
+        match validate_usage(building, &entry.usage) {
+            Ok((label, state)) => {
+                profile.counts.cost += building.cost;
+                match state {
+                    OwnedState::Storage => {
+                        profile.counts.warehouse_count += building.warehouse_count
+                    }
+                    OwnedState::Lodging => profile.counts.worker_count += building.worker_count,
+                    OwnedState::Other => {}
+                }
+                profile.buildings.push((key, label, state));
+            }
+            Err(e) => problems.push(format!("{region_name}: {e:#}").replace('\n', "\n  ")),
+        }
+    }
+
+    for (key, _, _) in profile.buildings.iter() {
+        let building = buildings.get(key).unwrap();
+        if let Some(parent) = buildings.get(&building.need_key) {
+            if !profile.buildings.iter().any(|(k, _, _)| *k == parent.key) {
+                problems.push(format!(
+                    "{region_name}: {} ({key}) requires {} ({}) which is not in the profile.",
+                    building.building_name, parent.building_name, parent.key
+                ));
+            }
+        }
+    }
+
+    // CP of the storage/lodging buildings along with the buildings they require.
+    let mut keys = BTreeSet::new();
+    for (key, _, state) in profile.buildings.iter() {
+        if *state != OwnedState::Other {
+            keys.insert(*key);
+            keys.extend(building_ancestors(*key, buildings).iter().map(|b| b.key));
+        }
+    }
+    profile.housing_cost = keys.iter().map(|key| buildings[key].cost).sum();
+    profile
+}
+
+/// Returns the validated region profiles of the profile file at `path`.
+///
+/// Every problem found (unknown regions or buildings, usages not offered and missing
+/// prerequisites) is reported together.
+pub(crate) fn load_profile(
+    path: &str,
+    regions_buildings: &RegionBuildingMap,
+) -> Result<Vec<RegionProfile>> {
+    let profile_file = read_profile(path)?;
+    let mut problems = vec![];
+    let mut profiles = vec![];
+    for (region, entries) in profile_file.iter() {
+        let region_name = match resolve_region_name(regions_buildings, region) {
+            Ok(name) => name,
+            Err(e) => {
+                problems.push(e.to_string().replace('\n', "\n  "));
+                continue;
+            }
+        };
+        let buildings = &regions_buildings[&region_name];
+        profiles.push(validate_region(
+            &region_name,
+            buildings,
+            entries,
+            &mut problems,
+        ));
+    }
+    if !problems.is_empty() {
+        bail!(
+            "The profile {} has {} problem(s):\n  {}",
+            path,
+            problems.len(),
+            problems.join("\n  ")
+        );
+    }
+    Ok(profiles)
+}
+
+fn optimal_cost(profile: &RegionProfile) -> Option<usize> {
+    // Regions without generated chains have no optimal reference.
+    let chains = Chain::many_from_region_json(&profile.region_name).ok()?;
+    chains
+        .iter()
+        .filter(|chain| {
+            chain.storage as usize >= profile.counts.warehouse_count
+                && chain.lodging as usize >= profile.counts.worker_count
+        })
+        .map(|chain| chain.cost as usize)
+        .min()
+}
+
+fn print_profile(profiles: &[RegionProfile]) {
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Region").add_attribute(Attribute::Dim),
+        Cell::new("Buildings").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+        Cell::new("S/L C").add_attribute(Attribute::Dim),
+        Cell::new("Optimal C").add_attribute(Attribute::Dim),
+        Cell::new("Gap").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut totals = UsageCounters::new();
+    let mut building_count = 0;
+    for profile in profiles.iter() {
+        totals.cost += profile.counts.cost;
+        totals.warehouse_count += profile.counts.warehouse_count;
+        totals.worker_count += profile.counts.worker_count;
+        building_count += profile.buildings.len();
+
+        let (optimal, gap) = match optimal_cost(profile) {
+            Some(cost) => {
+                let gap = profile.housing_cost as isize - cost as isize;
+                (cost.to_string(), gap.to_string())
+            }
+            None => ("".to_string(), "".to_string()),
+        };
+        table.add_row(vec![
+            &profile.region_name,
+            &profile.buildings.len().to_string(),
+            &profile.counts.cost.to_string(),
+            &profile.counts.warehouse_count.to_string(),
+            &profile.counts.worker_count.to_string(),
+            &profile.housing_cost.to_string(),
+            &optimal,
+            &gap,
+        ]);
+    }
+
+    table.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new(building_count).add_attribute(Attribute::Bold),
+        Cell::new(totals.cost).add_attribute(Attribute::Bold),
+        Cell::new(totals.warehouse_count).add_attribute(Attribute::Bold),
+        Cell::new(totals.worker_count).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+    ]);
+    println!("\n{table}");
+}
+
+fn print_region_details(profile: &RegionProfile, buildings: &BuildingMap) {
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("Usage").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+    ]);
+    for (key, label, state) in profile.buildings.iter() {
+        let building = &buildings[key];
+        let (warehouse_count, worker_count) = match state {
+            OwnedState::Storage => (building.warehouse_count.to_string(), "".to_string()),
+            OwnedState::Lodging => ("".to_string(), building.worker_count.to_string()),
+            OwnedState::Other => ("".to_string(), "".to_string()),
+        };
+        table.add_row(vec![
+            &key.to_string(),
+            &building.building_name,
+            label,
+            &building.cost.to_string(),
+            &warehouse_count,
+            &worker_count,
+        ]);
+    }
+    println!("\n{}\n{table}", profile.region_name);
+}
+
+/// Validates a housing profile and prints its per-region and total CP, storage and lodging
+/// along with each region's distance from the optimal chain at the same storage and lodging.
+pub(crate) fn show_profile(path: &str, region: Option<String>) -> Result<()> {
+    let regions_buildings = parse_houseinfo_data()?;
+    let mut profiles = load_profile(path, &regions_buildings)?;
+    if let Some(region) = region {
+        let region_name = resolve_region_name(&regions_buildings, &region)?;
+        profiles.retain(|p| p.region_name == region_name);
+        if profiles.is_empty() {
+            bail!("The profile {} has no buildings in {}.", path, region_name);
+        }
+    }
+    for profile in profiles.iter() {
+        print_region_details(profile, &regions_buildings[&profile.region_name]);
+    }
+    print_profile(&profiles);
+    Ok(())
+}
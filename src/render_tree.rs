@@ -0,0 +1,153 @@
+//! Render a region's building arborescence.
+//!
+//! The tree is drawn in the same traversal pre-order `RegionNodes` uses for generation and each
+//! building is labelled with its name, cost, storage and lodging. When a storage or lodging
+//! minimum is given the chain answering that query is highlighted along with the state each of
+//! its buildings is used in.
+
+use std::collections::HashMap;
+
+use anyhow::{Ok, Result};
+use console::style;
+
+use crate::cli_args::{Cli, TreeFormat};
+use crate::houseinfo::*;
+use crate::list_buildings::{filter_by_storage_and_lodging, Chain};
+use crate::node_manipulation::group_indices_by_value;
+use crate::region_nodes::RegionNodes;
+use crate::selection::BuildingSelection;
+
+type ChosenStates = HashMap<usize, usize>;
+
+fn chosen_states(cli: &Cli, region: &RegionNodes) -> Result<ChosenStates> {
+    let mut chosen = ChosenStates::new();
+    if cli.storage.is_none() && cli.lodging.is_none() {
+        return Ok(chosen);
+    }
+    let selection = BuildingSelection::from_cli(cli)?.for_region(&region.buildings)?;
+    let mut chains = Chain::many_from_selection_json(&region.region_name, &selection)?;
+    filter_by_storage_and_lodging(&mut chains, cli, &region.region_name)?;
+    if let Some(chain) = chains.first() {
+        if chains.len() > 1 {
+            info!(
+                "{} chains share the minimum cost; highlighting the first.",
+                chains.len()
+            );
+        }
+        for (index, state) in chain.indices.iter().zip(chain.states.iter()).skip(1) {
+            chosen.insert(*index, *state);
+        }
+    }
+    Ok(chosen)
+}
+
+fn state_name(state: usize) -> &'static str {
+    match state {
+        1 => "storage",
+        _ => "lodging",
+    }
+}
+
+fn label(building: &Building) -> String {
+    format!(
+        "{} ({}) C:{} S:{} L:{}",
+        building.building_name,
+        building.key,
+        building.cost,
+        building.warehouse_count,
+        building.worker_count
+    )
+}
+
+fn render_ascii(region: &RegionNodes, chosen: &ChosenStates) -> String {
+    let child_indices = group_indices_by_value(&region.parents);
+    let mut lines = vec![style(&region.region_name).bold().to_string()];
+    let mut stack: Vec<(usize, String, bool)> = vec![];
+
+    if let Some(indices) = child_indices.get(&region.root) {
+        for (i, index) in indices.iter().enumerate().rev() {
+            stack.push((*index, "".to_string(), i + 1 == indices.len()));
+        }
+    }
+
+    while let Some((index, prefix, is_last)) = stack.pop() {
+        let key = region.children[index];
+        let building = region.buildings.get(&key).unwrap();
+        let branch = if is_last { "└── " } else { "├── " };
+        let line = match chosen.get(&key) {
+            Some(state) => style(format!("{} [{}]", label(building), state_name(*state)))
+                .bold()
+                .green()
+                .to_string(),
+            None => label(building),
+        };
+        lines.push(format!("{prefix}{branch}{line}"));
+
+        let child_prefix = format!("{prefix}{}", if is_last { "    " } else { "│   " });
+        if let Some(indices) = child_indices.get(&key) {
+            for (i, index) in indices.iter().enumerate().rev() {
+                stack.push((*index, child_prefix.clone(), i + 1 == indices.len()));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+fn render_dot(region: &RegionNodes, chosen: &ChosenStates) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let mut lines = vec![
+        format!("digraph \"{}\" {{", escape(&region.region_name)),
+        "  node [shape=box, fontname=\"Helvetica\"];".to_string(),
+        format!(
+            "  \"{}\" [label=\"{}\", shape=ellipse];",
+            region.root,
+            escape(&region.region_name)
+        ),
+    ];
+
+    for key in region.children.iter().skip(1) {
+        let building = region.buildings.get(key).unwrap();
+        let label = format!(
+            "{}\\nCP {} | S {} | L {}",
+            escape(&building.building_name),
+            building.cost,
+            building.warehouse_count,
+            building.worker_count
+        );
+        let attributes = match chosen.get(key) {
+            Some(1) => ", style=filled, fillcolor=lightblue, penwidth=2",
+            Some(_) => ", style=filled, fillcolor=lightgoldenrod, penwidth=2",
+            None => "",
+        };
+        let label = match chosen.get(key) {
+            Some(state) => format!("{label}\\n[{}]", state_name(*state)),
+            None => label,
+        };
+        lines.push(format!("  \"{key}\" [label=\"{label}\"{attributes}];"));
+    }
+
+    for (parent, child) in region.parents.iter().zip(region.children.iter()).skip(1) {
+        let attributes = if chosen.contains_key(child) {
+            " [penwidth=2]"
+        } else {
+            ""
+        };
+        lines.push(format!("  \"{parent}\" -> \"{child}\"{attributes};"));
+    }
+    lines.push("}".to_string());
+    lines.join("\n")
+}
+
+/// Prints the region's building tree, highlighting the chain chosen by `-S/-L` if given.
+pub(crate) fn render_tree(cli: &Cli) -> Result<()> {
+    let region_buildings = get_region_buildings(cli.region.clone())?;
+    let buildings = region_buildings.values().next().unwrap();
+    let region = RegionNodes::new(buildings)?;
+    let chosen = chosen_states(cli, &region)?;
+
+    match cli.format {
+        TreeFormat::Ascii => println!("\n{}", render_ascii(&region, &chosen)),
+        TreeFormat::Dot => println!("{}", render_dot(&region, &chosen)),
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+//! Concurrent per-region work for `--generate` and `--optimize`.
+//!
+//! Regions run on a single rayon pool of `--jobs` threads (all cores by default) so the
+//! parallel work inside a region (generator jobs, optimizer lodging levels) shares the same
+//! thread budget as the regions themselves. Regions are started largest first by their
+//! multistate subtree count so the longest runs aren't left for last, and a region that fails
+//! (or panics) is reported without stopping the others.
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::cli_args::Cli;
+use crate::houseinfo::*;
+use crate::node_manipulation::count_subtrees_multistate;
+use crate::region_nodes::RegionNodes;
+
+pub(crate) type RegionResults<T> = Vec<(String, Result<T>)>;
+
+/// Returns the number of threads to use for `cli`.
+pub(crate) fn thread_budget(cli: &Cli) -> usize {
+    cli.jobs
+        .map(|jobs| jobs.max(1) as usize)
+        .unwrap_or_else(num_cpus::get)
+}
+
+fn largest_first(regions: &RegionBuildingMap) -> Vec<(&String, &BuildingMap)> {
+    let mut sized: Vec<(f64, &String, &BuildingMap)> = regions
+        .iter()
+        .map(|(region_name, buildings)| {
+            let size = RegionNodes::new(buildings)
+                .map(|region| {
+                    count_subtrees_multistate(
+                        region.root,
+                        &region.parents,
+                        &region.children,
+                        &region.states,
+                    )
+                })
+                .unwrap_or(0.0);
+            (size, region_name, buildings)
+        })
+        .collect();
+    sized.sort_by(|a, b| b.0.total_cmp(&a.0));
+    sized
+        .into_iter()
+        .map(|(_, region_name, buildings)| (region_name, buildings))
+        .collect()
+}
+
+/// Runs `work` for each region, largest first, with up to the `--jobs` thread budget and
+/// returns each region's result in that order.
+pub(crate) fn for_each_region<T, F>(
+    cli: &Cli,
+    regions: &RegionBuildingMap,
+    work: F,
+) -> RegionResults<T>
+where
+    T: Send,
+    F: Fn(&str, &BuildingMap) -> Result<T> + Sync,
+{
+    let ordered = largest_first(regions);
+    let threads = thread_budget(cli);
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .expect("Unable to build the region thread pool");
+    info!(
+        "running {} region(s) on {} thread(s)...",
+        ordered.len(),
+        threads
+    );
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<T>>>> =
+        Mutex::new((0..ordered.len()).map(|_| None).collect());
+    pool.scope(|scope| {
+        for _ in 0..threads.min(ordered.len()) {
+            scope.spawn(|_| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some((region_name, buildings)) = ordered.get(i) else {
+                    break;
+                };
+                let result = catch_unwind(AssertUnwindSafe(|| work(region_name, buildings)))
+                    .unwrap_or_else(|_| Err(anyhow!("Panicked; see the message above.")));
+                if let Err(e) = &result {
+                    error!("{}: {:#}", region_name, e);
+                }
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+
+    ordered
+        .iter()
+        .zip(results.into_inner().unwrap())
+        .map(|((region_name, _), result)| ((*region_name).to_owned(), result.unwrap()))
+        .collect()
+}
+
+/// Fails listing the regions in `results` that failed, if any.
+pub(crate) fn check_region_results<T>(results: &RegionResults<T>) -> Result<()> {
+    let failed: Vec<String> = results
+        .iter()
+        .filter_map(|(region_name, result)| {
+            result
+                .as_ref()
+                .err()
+                .map(|e| format!("{region_name}: {e:#}"))
+        })
+        .collect();
+    if !failed.is_empty() {
+        bail!(
+            "{} of {} region(s) failed:\n  {}",
+            failed.len(),
+            results.len(),
+            failed.join("\n  ")
+        );
+    }
+    anyhow::Ok(())
+}
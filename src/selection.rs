@@ -0,0 +1,168 @@
+//! Excluded and included buildings for generation and optimization.
+//!
+//! Excluding a building also excludes everything that requires it and including a building also
+//! includes its prerequisites. Chains generated with a region selection are written under
+//! `data/housecraft/custom` to a file named after the selection's buildings, so the canonical
+//! chain files are left untouched and listing with the same selection finds them.
+//!
+//! Each chain file has a `.fingerprint` file next to it holding a hash of the region's
+//! buildings when it was written, so listing can tell when `houseinfo.json` has changed since.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Ok, Result};
+
+use crate::cli_args::Cli;
+use crate::houseinfo::*;
+use crate::name_resolution::resolve_building_key;
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BuildingSelection {
+    pub excluded: BTreeSet<usize>,
+    pub included: BTreeSet<usize>,
+}
+
+impl BuildingSelection {
+    /// Returns the `--exclude` and `--include` building keys.
+    pub(crate) fn from_cli(cli: &Cli) -> Result<Self> {
+        let mut selection = Self::default();
+        if cli.exclude.is_none() && cli.include.is_none() {
+            return Ok(selection);
+        }
+        let regions_buildings = parse_houseinfo_data()?;
+        let buildings: Vec<&Building> = regions_buildings
+            .values()
+            .flat_map(|buildings| buildings.values())
+            .collect();
+        let parse = |list: &Option<String>| -> Result<BTreeSet<usize>> {
+            let mut keys = BTreeSet::new();
+            for entry in list.iter().flat_map(|l| l.split(',')) {
+                if !entry.trim().is_empty() {
+                    keys.insert(resolve_building_key(
+                        buildings.iter().copied(),
+                        entry.trim(),
+                    )?);
+                }
+            }
+            Ok(keys)
+        };
+        selection.excluded = parse(&cli.exclude)?;
+        selection.included = parse(&cli.include)?;
+        Ok(selection)
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.excluded.is_empty() && self.included.is_empty()
+    }
+
+    /// Returns the selection within `buildings` with excluded dependents and included
+    /// prerequisites added.
+    pub(crate) fn for_region(&self, buildings: &BuildingMap) -> Result<Self> {
+        let mut selection = Self::default();
+        for key in self.excluded.iter().filter(|k| buildings.contains_key(k)) {
+            selection.excluded.insert(*key);
+            selection.excluded.extend(
+                building_dependents(*key, buildings)
+                    .iter()
+                    .map(|(_, b)| b.key),
+            );
+        }
+        for key in self.included.iter().filter(|k| buildings.contains_key(k)) {
+            selection.included.insert(*key);
+            selection
+                .included
+                .extend(building_ancestors(*key, buildings).iter().map(|b| b.key));
+        }
+        if let Some(key) = selection.included.intersection(&selection.excluded).next() {
+            bail!(
+                "{} ({}) is both included and excluded.",
+                buildings[key].building_name,
+                key
+            );
+        }
+        Ok(selection)
+    }
+
+    /// Returns the file name for `region_name`'s chains made with this region selection.
+    ///
+    /// Selections are told apart by an FNV-1a hash of their excluded and included keys, which
+    /// stays the same across runs and builds.
+    pub(crate) fn file_name(&self, region_name: &str) -> String {
+        let region_name = region_name.replace(' ', "_");
+        if self.is_empty() {
+            return format!("{region_name}.json");
+        }
+        let keys = self.excluded.iter().map(|k| format!("x{k},"));
+        let keys = keys.chain(self.included.iter().map(|k| format!("i{k},")));
+        let hash = fnv1a(keys.flat_map(String::into_bytes));
+        format!("{region_name}_{hash:016x}.json")
+    }
+
+    /// Returns `buildings` without the excluded buildings.
+    pub(crate) fn retain_buildings(&self, buildings: &BuildingMap) -> Result<BuildingMap> {
+        let retained: BuildingMap = buildings
+            .iter()
+            .filter(|(key, _)| !self.excluded.contains(key))
+            .map(|(key, building)| (*key, building.clone()))
+            .collect();
+        if retained.is_empty() {
+            let region_name = &buildings.values().next().unwrap().region_name;
+            bail!("Every building in {} is excluded.", region_name);
+        }
+        Ok(retained)
+    }
+}
+
+/// Returns the chain file for `region_name` generated with the region's `selection`.
+pub(crate) fn chains_path(region_name: &str, selection: &BuildingSelection) -> PathBuf {
+    let dir = if selection.is_empty() {
+        "./data/housecraft"
+    } else {
+        "./data/housecraft/custom"
+    };
+    PathBuf::from(dir).join(selection.file_name(region_name))
+}
+
+/// Returns the fingerprint file written next to the chain file at `chains_path`.
+pub(crate) fn fingerprint_path(chains_path: &Path) -> PathBuf {
+    chains_path.with_extension("fingerprint")
+}
+
+/// Returns a hash of each building's key, prerequisite, cost and slots.
+///
+/// Any building added, removed or changed in `houseinfo.json` changes the fingerprint, whether
+/// or not a chain uses it.
+pub(crate) fn buildings_fingerprint(buildings: &BuildingMap) -> String {
+    let fields = buildings.values().map(|b| {
+        format!(
+            "{}:{}:{}:{}:{}:{},",
+            b.key, b.region_key, b.need_key, b.cost, b.warehouse_count, b.worker_count
+        )
+    });
+    format!("{:016x}", fnv1a(fields.flat_map(String::into_bytes)))
+}
+
+/// Writes the fingerprint of `buildings` next to the chain file at `chains_path`.
+pub(crate) fn write_fingerprint(chains_path: &Path, buildings: &BuildingMap) -> Result<()> {
+    fs::write(
+        fingerprint_path(chains_path),
+        buildings_fingerprint(buildings),
+    )?;
+    Ok(())
+}
+
+/// Whether the chain file at `chains_path` was written from the current `buildings`.
+///
+/// A missing fingerprint file counts as a mismatch.
+pub(crate) fn fingerprint_matches(chains_path: &Path, buildings: &BuildingMap) -> bool {
+    fs::read_to_string(fingerprint_path(chains_path))
+        .is_ok_and(|fingerprint| fingerprint.trim() == buildings_fingerprint(buildings))
+}
+
+fn fnv1a(bytes: impl Iterator<Item = u8>) -> u64 {
+    bytes.fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,123 @@
+use anyhow::{Ok, Result};
+use comfy_table::{Attribute, Cell, Table};
+use console::style;
+
+use crate::houseinfo::*;
+use crate::name_resolution::resolve_building_key;
+
+fn dim_header(table: &mut Table, names: &[&str]) {
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(
+        names
+            .iter()
+            .map(|name| Cell::new(name).add_attribute(Attribute::Dim))
+            .collect::<Vec<_>>(),
+    );
+}
+
+fn print_details(building: &Building) {
+    let mut table = Table::new();
+    dim_header(&mut table, &["Key", "Node", "Region", "Cost"]);
+    table.add_row(vec![
+        &building.key.to_string(),
+        &building.node_name,
+        &building.region_name,
+        &building.cost.to_string(),
+    ]);
+    println!("\n{}", style(&building.building_name).bold());
+    println!("{table}");
+}
+
+fn print_usages(building: &Building) {
+    let mut table = Table::new();
+    dim_header(&mut table, &["Crafting Usage", "Level", "Capacity"]);
+    for craft in building.craft_list.iter() {
+        if let Some(usage) = CRAFT_USAGE.get(&craft.item_craft_index) {
+            let capacity = match craft.level_to_count() {
+                0 => "".to_string(),
+                count => count.to_string(),
+            };
+            table.add_row(vec![usage, &craft.house_level.to_string(), &capacity]);
+        }
+    }
+    println!("\n{}", style("Usages").bold());
+    println!("{table}");
+}
+
+fn print_prerequisites(building: &Building, buildings: &BuildingMap) {
+    let mut chain = building_ancestors(building.key, buildings);
+    chain.reverse();
+    chain.push(building);
+
+    let mut table = Table::new();
+    dim_header(&mut table, &["Key", "Building", "C", "ttlC"]);
+    let mut total_cost = 0;
+    for link in chain.iter() {
+        total_cost += link.cost;
+        table.add_row(vec![
+            &link.key.to_string(),
+            &link.building_name,
+            &link.cost.to_string(),
+            &total_cost.to_string(),
+        ]);
+    }
+    println!(
+        "\n{}",
+        style(format!("Prerequisite chain from {}", building.region_name)).bold()
+    );
+    println!("{table}");
+}
+
+fn print_dependents(building: &Building, buildings: &BuildingMap) {
+    let dependents = building_dependents(building.key, buildings);
+    if dependents.is_empty() {
+        println!("\n{}", style("No dependents").bold());
+        return;
+    }
+
+    let mut table = Table::new();
+    dim_header(&mut table, &["Key", "Building", "C", "Requires"]);
+    for (depth, dependent) in dependents.iter() {
+        let requires = buildings
+            .get(&dependent.need_key)
+            .map(|parent| parent.building_name.clone())
+            .unwrap_or_default();
+        table.add_row(vec![
+            &dependent.key.to_string(),
+            &format!("{}{}", "  ".repeat(depth - 1), dependent.building_name),
+            &dependent.cost.to_string(),
+            &requires,
+        ]);
+    }
+    let direct = dependents.iter().filter(|(depth, _)| *depth == 1).count();
+    println!(
+        "\n{}",
+        style(format!(
+            "Dependents ({} direct, {} transitive)",
+            direct,
+            dependents.len() - direct
+        ))
+        .bold()
+    );
+    println!("{table}");
+}
+
+/// Shows a building's details, usages, prerequisite chain and dependents.
+pub(crate) fn show_building(region: Option<String>, building: String) -> Result<()> {
+    let regions_buildings = get_region_buildings(region)?;
+    let key = resolve_building_key(
+        regions_buildings.values().flat_map(|map| map.values()),
+        &building,
+    )?;
+    let buildings = regions_buildings
+        .values()
+        .find(|map| map.contains_key(&key))
+        .unwrap();
+    let building = buildings.get(&key).unwrap();
+
+    print_details(building);
+    print_usages(building);
+    print_prerequisites(building, buildings);
+    print_dependents(building, buildings);
+    Ok(())
+}
@@ -0,0 +1,284 @@
+//! Build the cross-region storage and lodging rankings.
+//!
+//! The storage-only chains (lodging 0) of every region in `all_lodging_storage.json` are merged
+//! into a single list ordered by vested contribution. A chain's contribution is the storage it
+//! adds over the region's previously taken chain for the CP it adds, so a region's later chains
+//! are only ranked by what they add once its earlier chains are paid for. Chains that add nothing
+//! are skipped. The result is written to `all_regions_storage.json` for `--list-storage`.
+//!
+//! The lodging ranking is built the same way from the cheapest chain of each lodging count and
+//! is written to `all_regions_lodging.json`.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+
+use anyhow::{Ok, Result};
+use regex::Regex;
+use serde::Serialize;
+use serde_json::to_string_pretty;
+
+use crate::allocate::{all_from_lodging_storage_json, RegionChainsMap};
+use crate::cli_args::Cli;
+use crate::list_buildings::Chain;
+
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct VestedChain {
+    pub lodging: u16,
+    pub storage: u16,
+    pub cost: u16,
+    pub indices: Vec<usize>,
+    pub states: Vec<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_storage: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_lodging: Option<u16>,
+    pub total_cost: u16,
+}
+
+fn extract_storage_only_chains(region_chains: &RegionChainsMap) -> Vec<&Chain> {
+    region_chains
+        .values()
+        .filter_map(|lodgings| lodgings.get(&0))
+        .flatten()
+        .filter(|chain| chain.storage > 0)
+        .collect()
+}
+
+fn extract_lodging_only_chains(region_chains: &RegionChainsMap) -> Vec<&Chain> {
+    // The cheapest chain providing each lodging count.
+    region_chains
+        .values()
+        .flat_map(|lodgings| lodgings.iter())
+        .filter(|(lodging, _)| **lodging > 0)
+        .filter_map(|(_, chains)| chains.iter().min_by_key(|c| (c.cost, c.storage)))
+        .collect()
+}
+
+/// Returns `chains` merged by vested contribution of `value` per CP along with the running
+/// total of `value`.
+///
+/// Ties on contribution go to the lower incremental cost and then to the lower region key.
+pub(crate) fn vested_dominance_merge(
+    chains: Vec<&Chain>,
+    value: fn(&Chain) -> u16,
+) -> Vec<(VestedChain, u16)> {
+    let mut region_value: HashMap<usize, i64> = HashMap::new();
+    let mut region_cost: HashMap<usize, i64> = HashMap::new();
+    let mut remaining_chains = chains;
+    let mut merged = vec![];
+
+    let increments =
+        |chain: &Chain, region_value: &HashMap<usize, i64>, region_cost: &HashMap<usize, i64>| {
+            let region = chain.indices[0];
+            (
+                value(chain) as i64 - region_value.get(&region).unwrap_or(&0),
+                chain.cost as i64 - region_cost.get(&region).unwrap_or(&0),
+            )
+        };
+
+    while !remaining_chains.is_empty() {
+        let mut best_index: Option<usize> = None;
+        let mut best_ratio = -1.0;
+
+        for (i, chain) in remaining_chains.iter().enumerate() {
+            let (incr_value, incr_cost) = increments(chain, &region_value, &region_cost);
+            if incr_value <= 0 || incr_cost < 0 {
+                // Already fully vested - simply skip rather than remove.
+                continue;
+            }
+
+            let ratio = if incr_cost > 0 {
+                incr_value as f64 / incr_cost as f64
+            } else {
+                f64::INFINITY
+            };
+            if ratio > best_ratio {
+                best_ratio = ratio;
+                best_index = Some(i);
+            } else if ratio == best_ratio {
+                let best = remaining_chains[best_index.unwrap()];
+                let (_, best_incr_cost) = increments(best, &region_value, &region_cost);
+                if incr_cost < best_incr_cost || chain.indices[0] < best.indices[0] {
+                    best_index = Some(i);
+                }
+            }
+        }
+
+        let Some(best_index) = best_index else {
+            break;
+        };
+
+        let chain = remaining_chains.remove(best_index);
+        let region = chain.indices[0];
+        region_value.insert(region, value(chain) as i64);
+        region_cost.insert(region, chain.cost as i64);
+
+        let total_value = region_value.values().sum::<i64>() as u16;
+        let vested = VestedChain {
+            lodging: chain.lodging,
+            storage: chain.storage,
+            cost: chain.cost,
+            indices: chain.indices.clone(),
+            states: chain.states.clone(),
+            total_storage: None,
+            total_lodging: None,
+            total_cost: region_cost.values().sum::<i64>() as u16,
+        };
+        merged.push((vested, total_value));
+    }
+    merged
+}
+
+fn write_vested_chains(cli: &Cli, filename: &str, chains: &[VestedChain]) -> Result<()> {
+    let path = PathBuf::from("./data/housecraft").join(filename);
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut output = File::create(path.clone())?;
+
+    let re = Regex::new(r"\{[^}]*?\}").unwrap();
+    let json = to_string_pretty(chains)?;
+    let json = re
+        .replace_all(&json, |caps: &regex::Captures<'_>| {
+            caps[0].replace(['\n', ' '], "")
+        })
+        .to_string();
+    output.write_all(json.as_bytes())?;
+
+    if !cli.verbose.is_silent() {
+        println!(
+            "Result: {} ranked chains written to {}.",
+            chains.len(),
+            path.to_str().unwrap()
+        );
+    }
+    Ok(())
+}
+
+/// Writes `all_regions_storage.json` and `all_regions_lodging.json` from
+/// `all_lodging_storage.json`.
+pub(crate) fn build_storage_index(cli: &Cli) -> Result<()> {
+    let region_chains = all_from_lodging_storage_json()?;
+
+    info!("ranking storage chains...");
+    let storage_only = extract_storage_only_chains(&region_chains);
+    let merged: Vec<_> = vested_dominance_merge(storage_only, |chain| chain.storage)
+        .into_iter()
+        .map(|(mut chain, total)| {
+            chain.total_storage = Some(total);
+            chain
+        })
+        .collect();
+    write_vested_chains(cli, "all_regions_storage.json", &merged)?;
+
+    info!("ranking lodging chains...");
+    let lodging_only = extract_lodging_only_chains(&region_chains);
+    let merged: Vec<_> = vested_dominance_merge(lodging_only, |chain| chain.lodging)
+        .into_iter()
+        .map(|(mut chain, total)| {
+            chain.total_lodging = Some(total);
+            chain
+        })
+        .collect();
+    write_vested_chains(cli, "all_regions_lodging.json", &merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize)]
+    struct RankedChain {
+        indices: Vec<usize>,
+        states: Vec<usize>,
+        total_storage: u16,
+        total_cost: u16,
+    }
+
+    /// Eight regions' storage-only chains from `all_lodging_storage.json` along with the ranking
+    /// `python/regenerate_all_regions_storage.py` produces for them.
+    #[derive(Debug, Deserialize)]
+    struct Fixture {
+        all_lodging_storage: RegionChainsMap,
+        python_ranking: Vec<RankedChain>,
+    }
+
+    fn chain(region: usize, cost: u16, storage: u16) -> Chain {
+        Chain {
+            lodging: 0,
+            storage,
+            cost,
+            indices: vec![region, cost as usize],
+            states: vec![0, 1],
+            alternatives: vec![],
+        }
+    }
+
+    fn ranking(chains: &[Chain]) -> Vec<(usize, u16, u16, u16)> {
+        vested_dominance_merge(chains.iter().collect(), |chain| chain.storage)
+            .into_iter()
+            .map(|(chain, total)| (chain.indices[0], chain.cost, total, chain.total_cost))
+            .collect()
+    }
+
+    #[test]
+    fn storage_ranking_matches_the_python_ranking() {
+        let fixture: Fixture =
+            serde_json::from_str(include_str!("testdata/vested_dominance_storage.json")).unwrap();
+        let storage_only = extract_storage_only_chains(&fixture.all_lodging_storage);
+        let merged = vested_dominance_merge(storage_only, |chain| chain.storage);
+
+        assert_eq!(merged.len(), fixture.python_ranking.len());
+        for (i, ((chain, total_storage), expected)) in
+            merged.iter().zip(fixture.python_ranking.iter()).enumerate()
+        {
+            assert_eq!(chain.indices, expected.indices, "ranking {i} indices");
+            assert_eq!(chain.states, expected.states, "ranking {i} states");
+            assert_eq!(
+                *total_storage, expected.total_storage,
+                "ranking {i} storage"
+            );
+            assert_eq!(chain.total_cost, expected.total_cost, "ranking {i} cost");
+        }
+    }
+
+    #[test]
+    fn merge_of_nothing_is_empty() {
+        assert!(ranking(&[]).is_empty());
+        assert!(ranking(&[chain(1, 1, 0)]).is_empty());
+    }
+
+    #[test]
+    fn merge_ranks_by_vested_contribution() {
+        // Region 2's second chain only adds 2 storage for 1 CP once its first is taken.
+        let chains = [chain(1, 2, 10), chain(2, 1, 8), chain(2, 2, 10)];
+        assert_eq!(
+            ranking(&chains),
+            vec![(2, 1, 8, 1), (1, 2, 18, 3), (2, 2, 20, 4)]
+        );
+    }
+
+    #[test]
+    fn merge_breaks_ratio_ties_by_cost_then_region() {
+        let chains = [chain(3, 2, 10), chain(2, 1, 5), chain(1, 1, 5)];
+        assert_eq!(
+            ranking(&chains),
+            vec![(1, 1, 5, 1), (2, 1, 10, 2), (3, 2, 20, 4)]
+        );
+    }
+
+    #[test]
+    fn merge_skips_chains_adding_nothing() {
+        // Once region 1's larger chain is taken its smaller one adds nothing.
+        let chains = [chain(1, 2, 20), chain(1, 3, 20), chain(1, 1, 4)];
+        assert_eq!(ranking(&chains), vec![(1, 2, 20, 2)]);
+    }
+
+    #[test]
+    fn merge_takes_free_increments_first() {
+        let chains = [chain(1, 1, 16), chain(2, 0, 3)];
+        assert_eq!(ranking(&chains), vec![(2, 0, 3, 0), (1, 1, 19, 1)]);
+    }
+}
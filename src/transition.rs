@@ -0,0 +1,258 @@
+//! Transition from one storage/lodging chain of a region to another.
+//!
+//! The starting point is either the cheapest chain reaching a target such as `S20L4` or the
+//! region's buildings in a housing profile; the destination is the cheapest chain reaching the
+//! `--to` target. Buildings are released children first, re-purposed, and then rented parents
+//! first so that no step leaves a rented building without its prerequisite.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+
+use anyhow::{Ok, Result};
+use comfy_table::{Attribute, Cell, Table};
+
+use crate::allocate::UsageTarget;
+use crate::cli_args::Cli;
+use crate::houseinfo::*;
+use crate::list_buildings::{Chain, InfeasibleQuery};
+use crate::owned::OwnedState;
+use crate::profile::load_profile;
+use crate::region_nodes::RegionNodes;
+
+type ChainStates = BTreeMap<usize, OwnedState>;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Action {
+    Release,
+    Switch,
+    Rent,
+}
+
+fn chain_states(chain: &Chain) -> ChainStates {
+    chain
+        .indices
+        .iter()
+        .zip(chain.states.iter())
+        .skip(1)
+        .map(|(key, state)| {
+            let state = if *state == 1 {
+                OwnedState::Storage
+            } else {
+                OwnedState::Lodging
+            };
+            (*key, state)
+        })
+        .collect()
+}
+
+fn cheapest_chain(region: &RegionNodes, target: &UsageTarget) -> Result<ChainStates> {
+    let chains = Chain::many_from_region_json(&region.region_name)?;
+    let Some(chain) = chains
+        .iter()
+        .filter(|chain| chain.storage >= target.storage && chain.lodging >= target.lodging)
+        .min_by_key(|chain| chain.cost)
+    else {
+        let error = InfeasibleQuery::from_chains(
+            &region.region_name,
+            target.storage,
+            target.lodging,
+            &chains,
+        );
+        return Err(error.into());
+    };
+    Ok(chain_states(chain))
+}
+
+fn starting_states(cli: &Cli, region: &RegionNodes, from: &str) -> Result<ChainStates> {
+    if !Path::new(from).is_file() {
+        return cheapest_chain(region, &UsageTarget::parse(from)?);
+    }
+    let regions_buildings = parse_houseinfo_data()?;
+    let profiles = load_profile(from, &regions_buildings)?;
+    let states: ChainStates = profiles
+        .iter()
+        .find(|profile| profile.region_name == region.region_name)
+        .map(|profile| {
+            profile
+                .buildings
+                .iter()
+                .map(|(key, _, state)| (*key, *state))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !cli.verbose.is_silent() && states.is_empty() {
+        println!(
+            "The profile {} has no buildings in {}.",
+            from, region.region_name
+        );
+    }
+    Ok(states)
+}
+
+fn transition_steps(
+    region: &RegionNodes,
+    from: &ChainStates,
+    to: &ChainStates,
+) -> Vec<(Action, usize, Option<OwnedState>, Option<OwnedState>)> {
+    // Buildings used for other things stay rented along with everything they require.
+    let mut kept: BTreeSet<usize> = to.keys().copied().collect();
+    for (key, state) in from.iter() {
+        if *state == OwnedState::Other && !to.contains_key(key) {
+            kept.insert(*key);
+            kept.extend(
+                building_ancestors(*key, &region.buildings)
+                    .iter()
+                    .map(|b| b.key),
+            );
+        }
+    }
+
+    let pre_order: HashMap<usize, usize> = region
+        .children
+        .iter()
+        .enumerate()
+        .map(|(i, key)| (*key, i))
+        .collect();
+    let mut steps = vec![];
+    for (key, state) in from.iter() {
+        if !kept.contains(key) {
+            steps.push((Action::Release, *key, Some(*state), None));
+        }
+    }
+    for (key, state) in to.iter() {
+        match from.get(key) {
+            Some(from_state) if from_state == state => {}
+            Some(from_state) => steps.push((Action::Switch, *key, Some(*from_state), Some(*state))),
+            None => steps.push((Action::Rent, *key, None, Some(*state))),
+        }
+    }
+
+    // Releases go deepest first and rents shallowest first.
+    steps.sort_by_key(|(action, key, _, _)| match action {
+        Action::Release => (0, usize::MAX - pre_order[key]),
+        Action::Switch => (1, pre_order[key]),
+        Action::Rent => (2, pre_order[key]),
+    });
+    steps
+}
+
+fn state_name(state: Option<OwnedState>) -> &'static str {
+    match state {
+        Some(OwnedState::Storage) => "storage",
+        Some(OwnedState::Lodging) => "lodging",
+        Some(OwnedState::Other) => "other",
+        None => "",
+    }
+}
+
+fn state_counts(region: &RegionNodes, states: &ChainStates) -> UsageCounters {
+    let mut counts = UsageCounters::new();
+    for (key, state) in states.iter() {
+        let building = &region.buildings[key];
+        counts.cost += building.cost;
+        match state {
+            OwnedState::Storage => counts.warehouse_count += building.warehouse_count,
+            OwnedState::Lodging => counts.worker_count += building.worker_count,
+            OwnedState::Other => {}
+        }
+    }
+    counts
+}
+
+fn print_transition(region: &RegionNodes, from: &ChainStates, to: &ChainStates) {
+    let steps = transition_steps(region, from, to);
+
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Step").add_attribute(Attribute::Dim),
+        Cell::new("Action").add_attribute(Attribute::Dim),
+        Cell::new("Key").add_attribute(Attribute::Dim),
+        Cell::new("Building").add_attribute(Attribute::Dim),
+        Cell::new("Usage").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut net_cost = 0isize;
+    for (step, (action, key, from_state, to_state)) in steps.iter().enumerate() {
+        let building = &region.buildings[key];
+        let (action, usage, cost) = match action {
+            Action::Release => (
+                "Release",
+                state_name(*from_state).to_string(),
+                -(building.cost as isize),
+            ),
+            Action::Switch => (
+                "Switch",
+                format!("{} → {}", state_name(*from_state), state_name(*to_state)),
+                0,
+            ),
+            Action::Rent => (
+                "Rent",
+                state_name(*to_state).to_string(),
+                building.cost as isize,
+            ),
+        };
+        net_cost += cost;
+        table.add_row(vec![
+            &(step + 1).to_string(),
+            action,
+            &key.to_string(),
+            &building.building_name,
+            &usage,
+            &if cost == 0 {
+                "".to_string()
+            } else {
+                format!("{cost:+}")
+            },
+        ]);
+    }
+
+    // The final state keeps the other-usage buildings that weren't released.
+    let mut final_states = to.clone();
+    for (key, state) in from.iter() {
+        let released = steps
+            .iter()
+            .any(|(action, k, _, _)| *action == Action::Release && k == key);
+        if !released && !final_states.contains_key(key) {
+            final_states.insert(*key, *state);
+        }
+    }
+    let from_counts = state_counts(region, from);
+    let to_counts = state_counts(region, &final_states);
+    table.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new(format!("{} steps", steps.len())).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new(format!(
+            "C {} S {} L {} → C {} S {} L {}",
+            from_counts.cost,
+            from_counts.warehouse_count,
+            from_counts.worker_count,
+            to_counts.cost,
+            to_counts.warehouse_count,
+            to_counts.worker_count
+        ))
+        .add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new(format!("{net_cost:+}")).add_attribute(Attribute::Bold),
+    ]);
+    println!("\n{table}");
+}
+
+/// Prints the ordered release, switch and rent steps from `--from` (a target or a housing
+/// profile) to the cheapest chain reaching the `--to` target.
+pub(crate) fn transition(cli: &Cli) -> Result<()> {
+    let region_buildings = get_region_buildings(cli.region.clone())?;
+    let (_, buildings) = region_buildings.first_key_value().unwrap();
+    let region = RegionNodes::new(buildings)?;
+
+    let from = starting_states(cli, &region, cli.from.as_ref().unwrap())?;
+    let to = cheapest_chain(&region, &UsageTarget::parse(cli.to.as_ref().unwrap())?)?;
+    if from == to {
+        println!("\nNo changes are needed.");
+        return Ok(());
+    }
+    print_transition(&region, &from, &to);
+    Ok(())
+}
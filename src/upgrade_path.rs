@@ -0,0 +1,214 @@
+//! Nested upgrade path through a region's storage/lodging front.
+//!
+//! The targets are the region's chains (at or above the -S/-L minimums and within the maximums)
+//! taken in cost order whenever they add storage or lodging to the previous target. Each step is
+//! solved with the `SubsetModel` with every building rented so far fixed as rented, so a step
+//! only ever adds buildings (usages may still switch). The cumulative CP is compared with the
+//! target chain's cost to show what keeping the earlier buildings costs.
+
+use anyhow::{bail, Ok, Result};
+use comfy_table::{Attribute, Cell, Table};
+use highs_sys::*;
+
+use crate::cli_args::Cli;
+use crate::houseinfo::*;
+use crate::list_buildings::Chain;
+use crate::optimize::SubsetModel;
+use crate::region_nodes::RegionNodes;
+
+struct PathStep {
+    target: (u16, u16, u16),
+    rented: Vec<usize>,
+    switched: usize,
+    counts: UsageCounters,
+}
+
+fn path_targets(cli: &Cli, chains: &[Chain]) -> Vec<(u16, u16, u16)> {
+    let storage = cli.storage.unwrap_or(0);
+    let lodging = cli.lodging.unwrap_or(0);
+    let max_cost = cli.max_cost.unwrap_or(u16::MAX);
+    let max_storage = cli.max_storage.unwrap_or(u16::MAX);
+    let max_lodging = cli.max_lodging.unwrap_or(u16::MAX);
+
+    let mut candidates: Vec<_> = chains
+        .iter()
+        .filter(|chain| {
+            (storage..=max_storage).contains(&chain.storage)
+                && (lodging..=max_lodging).contains(&chain.lodging)
+                && chain.cost <= max_cost
+                && chain.cost > 0
+        })
+        .map(|chain| (chain.storage, chain.lodging, chain.cost))
+        .collect();
+    candidates.sort_by_key(|(s, l, cost)| (*cost, *s, *l));
+
+    let mut targets: Vec<(u16, u16, u16)> = vec![];
+    for (s, l, cost) in candidates {
+        let grows = match targets.last() {
+            Some((ps, pl, _)) => s >= *ps && l >= *pl && (s, l) != (*ps, *pl),
+            None => true,
+        };
+        if grows {
+            targets.push((s, l, cost));
+        }
+    }
+    targets
+}
+
+fn solve_path(region: &RegionNodes, targets: &[(u16, u16, u16)]) -> Result<Vec<PathStep>> {
+    let mut model = SubsetModel::new(region, 0);
+    let (state_1_sum_row, state_2_sum_row) = model.state_sum_rows();
+    let highs = model.mut_ptr();
+    let highs_inf = unsafe { Highs_getInfinity(highs) };
+
+    // Node states of the previous step, 0 when not rented.
+    let mut previous = vec![0; region.num_nodes];
+    let mut steps = vec![];
+    for (storage, lodging, cost) in targets.iter() {
+        let status = unsafe {
+            Highs_changeRowBounds(highs, state_1_sum_row, *storage as f64, highs_inf);
+            Highs_changeRowBounds(highs, state_2_sum_row, *lodging as f64, highs_inf);
+            Highs_run(highs);
+            Highs_getModelStatus(highs)
+        };
+        if status != MODEL_STATUS_OPTIMAL {
+            bail!(
+                "No superset of the previous step reaches {} storage and {} lodging ({} CP).",
+                storage,
+                lodging,
+                cost
+            );
+        }
+
+        let col_values = model.solution_col_values();
+        let mut step = PathStep {
+            target: (*storage, *lodging, *cost),
+            rented: vec![],
+            switched: 0,
+            counts: UsageCounters::new(),
+        };
+        for (i, col) in col_values
+            .chunks_exact(3)
+            .take(region.num_nodes)
+            .enumerate()
+            .skip(1)
+        {
+            if col[0] != 1 {
+                continue;
+            }
+            let state = if col[1] == 1 { 1 } else { 2 };
+            let building = &region.buildings[&region.children[i]];
+            step.counts.cost += building.cost;
+            if state == 1 {
+                step.counts.warehouse_count += building.warehouse_count;
+            } else {
+                step.counts.worker_count += building.worker_count;
+            }
+            match previous[i] {
+                0 => {
+                    step.rented.push(building.key);
+                    // Rented from now on.
+                    let item_flag = model.item_flags[&(building.key as u32)];
+                    unsafe { Highs_changeColBounds(highs, item_flag, 1.0, 1.0) };
+                }
+                previous_state if previous_state != state => step.switched += 1,
+                _ => {}
+            }
+            previous[i] = state;
+        }
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+fn print_path(region: &RegionNodes, steps: &[PathStep]) {
+    let mut table = Table::new();
+    table.load_preset(HOUSECRAFT_TABLE_STYLE);
+    table.set_header(vec![
+        Cell::new("Step").add_attribute(Attribute::Dim),
+        Cell::new("Target").add_attribute(Attribute::Dim),
+        Cell::new("Rent").add_attribute(Attribute::Dim),
+        Cell::new("Switch").add_attribute(Attribute::Dim),
+        Cell::new("C").add_attribute(Attribute::Dim),
+        Cell::new("S").add_attribute(Attribute::Dim),
+        Cell::new("L").add_attribute(Attribute::Dim),
+        Cell::new("Front C").add_attribute(Attribute::Dim),
+        Cell::new("Extra").add_attribute(Attribute::Dim),
+    ]);
+
+    let mut max_extra = 0;
+    for (i, step) in steps.iter().enumerate() {
+        let (storage, lodging, front_cost) = step.target;
+        // Only a chain file that isn't the exact front (made with --limit-warehouse, say) can
+        // have a step cheaper than the front chain it reaches, so the difference is signed.
+        let extra = step.counts.cost as isize - front_cost as isize;
+        debug_assert!(
+            i == 0 || step.counts.cost >= steps[i - 1].counts.cost,
+            "a step only adds buildings"
+        );
+        max_extra = max_extra.max(extra);
+        let rented: Vec<String> = step
+            .rented
+            .iter()
+            .map(|key| format!("{} {}", key, region.buildings[key].building_name))
+            .collect();
+        table.add_row(vec![
+            Cell::new(i + 1),
+            Cell::new(format!("S{storage} L{lodging}")),
+            Cell::new(rented.join("\n")),
+            Cell::new(if step.switched > 0 {
+                step.switched.to_string()
+            } else {
+                "".to_string()
+            }),
+            Cell::new(step.counts.cost),
+            Cell::new(step.counts.warehouse_count),
+            Cell::new(step.counts.worker_count),
+            Cell::new(front_cost),
+            Cell::new(if extra != 0 {
+                format!("{extra:+}")
+            } else {
+                "".to_string()
+            }),
+        ]);
+    }
+
+    let last = steps.last().unwrap();
+    table.add_row(vec![
+        Cell::new("Totals").add_attribute(Attribute::Bold),
+        Cell::new(format!("{} steps", steps.len())).add_attribute(Attribute::Bold),
+        Cell::new(format!(
+            "{} rentals",
+            steps.iter().map(|s| s.rented.len()).sum::<usize>()
+        ))
+        .add_attribute(Attribute::Bold),
+        Cell::new(steps.iter().map(|s| s.switched).sum::<usize>()).add_attribute(Attribute::Bold),
+        Cell::new(last.counts.cost).add_attribute(Attribute::Bold),
+        Cell::new(last.counts.warehouse_count).add_attribute(Attribute::Bold),
+        Cell::new(last.counts.worker_count).add_attribute(Attribute::Bold),
+        Cell::new("").add_attribute(Attribute::Bold),
+        Cell::new(format!("max +{max_extra}")).add_attribute(Attribute::Bold),
+    ]);
+    println!("\n{table}");
+}
+
+/// Prints a sequence of growing chains for the region where each step only adds buildings,
+/// along with each step's extra CP over the front chain it reaches.
+pub(crate) fn upgrade_path(cli: &Cli) -> Result<()> {
+    let region_buildings = get_region_buildings(cli.region.clone())?;
+    let (region_name, buildings) = region_buildings.first_key_value().unwrap();
+    let region = RegionNodes::new(buildings)?;
+
+    let chains = Chain::many_from_region_json(region_name)?;
+    let targets = path_targets(cli, &chains);
+    if targets.is_empty() {
+        bail!(
+            "No {} chain is within the -S/-L minimums and maximums.",
+            region_name
+        );
+    }
+    info!("solving {} upgrade steps...", targets.len());
+    let steps = solve_path(&region, &targets)?;
+    print_path(&region, &steps);
+    Ok(())
+}